@@ -0,0 +1,382 @@
+// Mermaid, PlantUML and Graphviz blocks rewritten as edge lists, one line of
+// `A→B: label` entries separated by `; `. Nodes are shown by their label when
+// the diagram gives one. Undirected links use `↔`. Styling, layout and nodes
+// without edges are dropped; blocks with no recognizable edges are left alone.
+
+use crate::fences;
+use regex::Regex;
+use std::collections::HashMap;
+
+struct Edge {
+    from: String,
+    to: String,
+    label: String,
+    directed: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    edges: Vec<Edge>,
+    labels: HashMap<String, String>, // Node id → display label
+}
+
+impl Graph {
+    fn edge(&mut self, from: &str, to: &str, label: &str, directed: bool) {
+        self.edges.push(Edge { from: from.to_string(), to: to.to_string(), label: label.trim().to_string(), directed });
+    }
+
+    fn label(&mut self, id: &str, label: &str) {
+        let label = label.trim().trim_matches('"').trim();
+        if !label.is_empty() && label != id {
+            self.labels.entry(id.to_string()).or_insert_with(|| label.to_string());
+        }
+    }
+
+    fn render(&self) -> Option<String> {
+        if self.edges.is_empty() {
+            return None;
+        }
+        let name = |id: &String| self.labels.get(id).unwrap_or(id).clone();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let arrow = if edge.directed { "→" } else { "↔" };
+                let mut line = format!("{}{}{}", name(&edge.from), arrow, name(&edge.to));
+                if !edge.label.is_empty() {
+                    line.push_str(": ");
+                    line.push_str(&edge.label);
+                }
+                line
+            })
+            .collect();
+        Some(edges.join("; "))
+    }
+}
+
+/// Rewrite every diagram fence in `content`. Returns the new content and the
+/// number of diagrams converted.
+pub fn compress_diagrams(content: &str) -> (String, usize) {
+    let mut converted = 0;
+    let result = fences::map_blocks(content, |block| {
+        let graph = match block.lang.as_str() {
+            "mermaid" => parse_mermaid(&block.body),
+            "plantuml" | "puml" | "uml" => parse_plantuml(&block.body),
+            "dot" | "graphviz" | "gv" => parse_dot(&block.body.join("\n")),
+            _ => return None,
+        };
+        let edges = graph.render()?;
+        converted += 1;
+        Some(vec![block.open.to_string(), edges])
+    });
+    (result, converted)
+}
+
+fn parse_mermaid(body: &[&str]) -> Graph {
+    let mut graph = Graph::default();
+    let lines: Vec<&str> = body.iter().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with("%%")).collect();
+    let Some(kind) = lines.first().and_then(|l| l.split_whitespace().next()) else {
+        return graph;
+    };
+
+    match kind {
+        "graph" | "flowchart" => lines[1..].iter().for_each(|line| parse_flowchart_line(line, &mut graph)),
+        "sequenceDiagram" => {
+            let re_participant = Regex::new(r"^(?:participant|actor)\s+(\S+)\s+as\s+(.+)$").unwrap();
+            let re_message = Regex::new(r"^([^\s:+-][^:]*?)\s*(?:--?>>|--?>|--?x|--?\))\s*[+-]?\s*([^:]+?)\s*:\s*(.*)$").unwrap();
+            for line in &lines[1..] {
+                if let Some(caps) = re_participant.captures(line) {
+                    graph.label(&caps[1], &caps[2]);
+                } else if let Some(caps) = re_message.captures(line) {
+                    graph.edge(&caps[1], &caps[2], &caps[3], true);
+                }
+            }
+        }
+        "classDiagram" => {
+            let re_relation = Regex::new(
+                r#"^([\w.]+)\s*(?:"[^"]*"\s*)?(<\|--|--\|>|<\|\.\.|\.\.\|>|\*--|--\*|o--|--o|-->|<--|\.\.>|<\.\.|--|\.\.)\s*(?:"[^"]*"\s*)?([\w.]+)\s*(?::\s*(.*))?$"#,
+            )
+            .unwrap();
+            for line in &lines[1..] {
+                let Some(caps) = re_relation.captures(line) else { continue };
+                let (a, b, label) = (&caps[1], &caps[3], caps.get(4).map_or("", |m| m.as_str()));
+                let (from, to, kind) = match &caps[2] {
+                    "<|--" => (b, a, "extends"),
+                    "--|>" => (a, b, "extends"),
+                    "<|.." => (b, a, "implements"),
+                    "..|>" => (a, b, "implements"),
+                    "*--" | "o--" => (a, b, "has"),
+                    "--*" | "--o" => (b, a, "has"),
+                    "..>" => (a, b, "uses"),
+                    "<.." => (b, a, "uses"),
+                    "<--" => (b, a, ""),
+                    "-->" => (a, b, ""),
+                    _ => {
+                        graph.edge(a, b, label, false);
+                        continue;
+                    }
+                };
+                graph.edge(from, to, if label.is_empty() { kind } else { label }, true);
+            }
+        }
+        "erDiagram" => {
+            let re_relation = Regex::new(r#"^([\w-]+)\s+[|}o][|o]?(?:--|\.\.)[|o][|{o]?\s+([\w-]+)\s*:\s*"?([^"]*)"?$"#).unwrap();
+            for line in &lines[1..] {
+                if let Some(caps) = re_relation.captures(line) {
+                    graph.edge(&caps[1], &caps[2], &caps[3], true);
+                }
+            }
+        }
+        "stateDiagram" | "stateDiagram-v2" => {
+            let re_transition = Regex::new(r"^(\S+)\s*-->\s*([^:\s]+)\s*(?::\s*(.*))?$").unwrap();
+            for line in &lines[1..] {
+                if let Some(caps) = re_transition.captures(line) {
+                    let state = |s: &str| if s == "[*]" { "●".to_string() } else { s.to_string() };
+                    graph.edge(&state(&caps[1]), &state(&caps[2]), caps.get(3).map_or("", |m| m.as_str()), true);
+                }
+            }
+        }
+        _ => {}
+    }
+    graph
+}
+
+/// One flowchart statement: `A[Start] -->|go| B & C -- no --> D{Done?}`.
+fn parse_flowchart_line(line: &str, graph: &mut Graph) {
+    // `A -- text --> B` is the same edge as `A -->|text| B`
+    let re_text_label = Regex::new(r"\s(--|==|-\.)\s+([^|>]+?)\s+(-{2,}>|={2,}>|\.-+>|-{3,}|={3,}|\.-+)\s").unwrap();
+    let re_operator = Regex::new(r"^\s*(<?(?:-{2,}|={2,}|-\.+-)[>xo]?)\s*(?:\|([^|]*)\|)?\s*").unwrap();
+    let line = re_text_label.replace_all(line, " $3|$2| ");
+
+    let mut rest: &str = &line;
+    let Some(mut previous) = parse_nodes(&mut rest, graph) else { return };
+    while let Some(caps) = re_operator.captures(rest) {
+        let operator = caps[1].to_string();
+        let label = caps.get(2).map_or("", |m| m.as_str()).to_string();
+        rest = &rest[caps[0].len()..];
+        let Some(next) = parse_nodes(&mut rest, graph) else { return };
+
+        let directed = operator.ends_with('>') && !operator.starts_with('<');
+        for from in &previous {
+            for to in &next {
+                graph.edge(from, to, label.trim_matches('"'), directed);
+            }
+        }
+        previous = next;
+    }
+}
+
+/// Node ids joined with `&`, recording the label of any shaped node
+/// (`A[Label]`, `B(Label)`, `C{Label}`, `D>Label]`).
+fn parse_nodes(rest: &mut &str, graph: &mut Graph) -> Option<Vec<String>> {
+    let mut ids = Vec::new();
+    loop {
+        let text = rest.trim_start();
+        let id_len = text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).unwrap_or(text.len());
+        if id_len == 0 {
+            return None;
+        }
+        let id = &text[..id_len];
+        let mut after = &text[id_len..];
+
+        let close = match after.chars().next() {
+            Some('[') => Some(']'),
+            Some('(') => Some(')'),
+            Some('{') => Some('}'),
+            Some('>') => Some(']'),
+            _ => None,
+        };
+        if let Some(close) = close {
+            let open = after.chars().next().unwrap();
+            // Doubled shapes like [[sub]] or ((circle)) nest the same bracket
+            let mut depth = 0;
+            let mut end = None;
+            for (i, c) in after.char_indices() {
+                if c == open && open != '>' {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth <= 0 {
+                        end = Some(i);
+                        break;
+                    }
+                }
+            }
+            let end = end?;
+            let label = after[1..end].trim_matches(|c| "[](){}/\\".contains(c));
+            graph.label(id, label);
+            after = &after[end + 1..];
+        }
+
+        ids.push(id.to_string());
+        *rest = after;
+        match after.trim_start().strip_prefix('&') {
+            Some(more) => *rest = more,
+            None => return Some(ids),
+        }
+    }
+}
+
+fn parse_plantuml(body: &[&str]) -> Graph {
+    let node = r#"("[^"]+"|\[[^\]]+\]|\([^)]+\)|:[^:]+:|[\w.]+)"#;
+    let re_alias = Regex::new(r#"^\w+\s+("[^"]+"|\[[^\]]+\]|\([^)]+\)|:[^:]+:)\s+as\s+([\w.]+)"#).unwrap();
+    let re_reverse_alias = Regex::new(r#"^\w+\s+([\w.]+)\s+as\s+("[^"]+")"#).unwrap();
+    let re_relation = Regex::new(&format!(
+        r"^{}\s*(<\|[-.]+|[-.]+\|>|\*[-.]+|o[-.]+|[-.]+\*|[-.]+o|<<?[-.]+(?:\w+[-.]+)?>?>?|[-.]+(?:\w+[-.]+)?>?>?)\s*{}\s*(?::\s*(.*))?$",
+        node, node
+    ))
+    .unwrap();
+    let name = |s: &str| s.trim_matches(|c| "\"[]():".contains(c)).trim().to_string();
+
+    let mut graph = Graph::default();
+    for line in body.iter().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('\'') || line.starts_with('@') {
+            continue;
+        }
+        if let Some(caps) = re_alias.captures(line) {
+            graph.label(&caps[2], &name(&caps[1]));
+            continue;
+        }
+        if let Some(caps) = re_reverse_alias.captures(line) {
+            graph.label(&caps[1], &name(&caps[2]));
+            continue;
+        }
+        let Some(caps) = re_relation.captures(line) else { continue };
+
+        let (a, b) = (name(&caps[1]), name(&caps[3]));
+        let label = caps.get(4).map_or("", |m| m.as_str());
+        let operator = &caps[2];
+        let (from, to, kind) = if operator.starts_with("<|") {
+            (b, a, "extends")
+        } else if operator.ends_with("|>") {
+            (a, b, "extends")
+        } else if operator.starts_with('<') && !operator.ends_with('>') {
+            (b, a, "")
+        } else {
+            (a, b, "")
+        };
+        let directed = operator.contains('>') || operator.contains('<');
+        graph.edge(&from, &to, if label.is_empty() { kind } else { label }, directed);
+    }
+    graph
+}
+
+fn parse_dot(body: &str) -> Graph {
+    let re_comment = Regex::new(r#"(?m)/\*(?s:.*?)\*/|(?:^|[^:"])//.*$|^\s*#.*$"#).unwrap();
+    let re_label = Regex::new(r#"\blabel\s*=\s*(?:"((?:[^"\\]|\\.)*)"|([\w.]+))"#).unwrap();
+    let re_edge_op = Regex::new(r"\s*(->|--)\s*").unwrap();
+    let node = |s: &str| {
+        let s = s.trim();
+        let s = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(s.split(':').next().unwrap_or(s));
+        s.to_string()
+    };
+
+    let mut graph = Graph::default();
+    let text = re_comment.replace_all(body, "");
+    for statement in split_statements(&text) {
+        let (body, attrs) = match statement.find('[') {
+            Some(i) => (&statement[..i], &statement[i..]),
+            None => (statement.as_str(), ""),
+        };
+        let label = re_label.captures(attrs).map(|caps| caps.get(1).or(caps.get(2)).map_or("", |m| m.as_str()).to_string());
+        let nodes: Vec<String> = re_edge_op.split(body).map(node).filter(|n| !n.is_empty()).collect();
+
+        if nodes.len() == 1 {
+            if let Some(label) = label
+                && !matches!(nodes[0].as_str(), "node" | "edge" | "graph") {
+                graph.label(&nodes[0], &label.replace("\\n", " "));
+            }
+            continue;
+        }
+        let directed = body.contains("->");
+        for pair in nodes.windows(2) {
+            graph.edge(&pair[0], &pair[1], label.as_deref().unwrap_or(""), directed);
+        }
+    }
+    graph
+}
+
+/// Statements split at `;`, newlines and braces, outside quotes and
+/// attribute lists, with graph/subgraph headers dropped.
+fn split_statements(text: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut in_quote = false;
+    let mut in_attrs = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '[' if !in_quote => in_attrs = true,
+            ']' if !in_quote => in_attrs = false,
+            ';' | '\n' | '{' | '}' if !in_quote && !in_attrs => {
+                statements.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| {
+            let header = matches!(s.split_whitespace().next(), Some("digraph" | "graph" | "subgraph" | "strict"));
+            let graph_attribute = s.contains('=') && !s.contains('['); // rankdir=LR
+            !s.is_empty() && !header && !graph_attribute
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(lang: &str, body: &str) -> String {
+        let (output, count) = compress_diagrams(&format!("```{}\n{}\n```\n", lang, body));
+        assert_eq!(count, 1, "{}", output);
+        fences::blocks(&output)[0].body.join("\n")
+    }
+
+    #[test]
+    fn flowchart_arrows_labels_and_fan_out() {
+        let body = "flowchart LR\n    A[Client] -->|HTTP| B(API gateway)\n    B --> C{Auth?} & D[(Cache)]\n    C -- no --> A\n    D --- E\n    style A fill:#f9f";
+        assert_eq!(
+            edges("mermaid", body),
+            "Client→API gateway: HTTP; API gateway→Auth?; API gateway→Cache; Auth?→Client: no; Cache↔E"
+        );
+    }
+
+    #[test]
+    fn sequence_participants_are_aliased() {
+        let body = "sequenceDiagram\n    participant U as User\n    actor S as Server\n    U->>+S: login\n    S-->>-U: token";
+        assert_eq!(edges("mermaid", body), "User→Server: login; Server→User: token");
+    }
+
+    #[test]
+    fn class_and_state_diagrams() {
+        assert_eq!(edges("mermaid", "classDiagram\n    Animal <|-- Duck\n    Duck *-- Wing\n    Duck .. Pond"), "Duck→Animal: extends; Duck→Wing: has; Duck↔Pond");
+        assert_eq!(edges("mermaid", "stateDiagram-v2\n    [*] --> Idle\n    Idle --> Busy : job"), "●→Idle; Idle→Busy: job");
+    }
+
+    #[test]
+    fn plantuml_aliases_and_arrows() {
+        let body = "@startuml\nactor \"Web User\" as U\nparticipant API\nU -> API : request\nAPI <-- DB\n@enduml";
+        assert_eq!(edges("plantuml", body), "Web User→API: request; DB→API");
+    }
+
+    #[test]
+    fn graphviz_labels_and_comments() {
+        let body = "digraph G {\n  // services\n  a [label=\"Web\"];\n  a -> b [label=\"calls\"];\n  b -> c; /* db */\n}";
+        assert_eq!(edges("dot", body), "Web→b: calls; b→c");
+        assert_eq!(edges("dot", "graph { x -- y }"), "x↔y");
+    }
+
+    #[test]
+    fn diagrams_without_edges_are_left_alone() {
+        let content = "```mermaid\npie title Pets\n    \"Dogs\" : 386\n```\n";
+        assert_eq!(compress_diagrams(content), (content.to_string(), 0));
+    }
+}
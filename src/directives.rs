@@ -0,0 +1,192 @@
+// In-document directives, written as HTML comments on a line of their own:
+//
+//   <!-- verdant:skip --> ... <!-- verdant:end -->      left out of the bundle
+//   <!-- verdant:verbatim --> ... <!-- verdant:end -->  passed through untouched
+//   <!-- verdant:level low --> ... <!-- verdant:end --> compressed at another level
+//   <!-- verdant:priority high -->                      priority of the whole file
+//
+// Regions without an end marker run to the end of the file, so a level or
+// verbatim directive at the top applies to the whole document.
+
+use crate::fences;
+use regex::Regex;
+
+pub const LEVELS: [&str; 4] = ["low", "medium", "high", "extreme"];
+pub const PRIORITIES: [&str; 3] = ["high", "normal", "low"];
+
+// Canonical markers left in the content for compress_regions
+const LEVEL_MARKER: &str = "<!--verdant:level ";
+const END_MARKER: &str = "<!--verdant:end-->";
+
+pub struct FileDirectives {
+    pub priority: Option<String>,
+    pub verbatim: Vec<(usize, String)>, // (placeholder id, original text)
+    pub skipped_lines: usize,
+}
+
+enum Region {
+    None,
+    Skip,
+    Verbatim(Vec<String>),
+    Level,
+}
+
+/// Drop skip regions, stash verbatim regions behind placeholders and
+/// normalize level markers. `next_id` numbers placeholders across the bundle
+/// so duplicate removal never merges two of them.
+pub fn apply(content: &str, name: &str, next_id: &mut usize) -> (String, FileDirectives) {
+    let re_directive = Regex::new(r"^\s*<!--\s*verdant:([a-z]+)(?:\s+([a-z]+))?\s*-->\s*$").unwrap();
+    let mut directives = FileDirectives { priority: None, verbatim: Vec::new(), skipped_lines: 0 };
+    let mut output: Vec<String> = Vec::new();
+    let mut region = Region::None;
+    let in_code = fences::code_lines(content);
+
+    for (i, line) in content.lines().enumerate() {
+        let directive = if in_code[i] { None } else { re_directive.captures(line) };
+
+        let Some(caps) = directive else {
+            match &mut region {
+                Region::Skip => directives.skipped_lines += 1,
+                Region::Verbatim(lines) => lines.push(line.to_string()),
+                Region::None | Region::Level => output.push(line.to_string()),
+            }
+            continue;
+        };
+
+        let value = caps.get(2).map(|m| m.as_str());
+        match (&caps[1], value) {
+            ("end", None) => {
+                match std::mem::replace(&mut region, Region::None) {
+                    Region::Verbatim(lines) => stash(lines, &mut output, &mut directives, next_id),
+                    Region::Level => output.push(END_MARKER.to_string()),
+                    Region::Skip => {}
+                    Region::None => eprintln!("⚠️  Unmatched verdant:end in {}", name),
+                }
+            }
+            // Inside skip and verbatim regions only the end marker counts
+            _ if matches!(region, Region::Skip) => directives.skipped_lines += 1,
+            _ if matches!(region, Region::Verbatim(_)) => {
+                if let Region::Verbatim(lines) = &mut region {
+                    lines.push(line.to_string());
+                }
+            }
+            ("skip", None) => region = Region::Skip,
+            ("verbatim", None) => region = Region::Verbatim(Vec::new()),
+            ("level", Some(level)) if LEVELS.contains(&level) => {
+                output.push(format!("{}{}-->", LEVEL_MARKER, level));
+                region = Region::Level;
+            }
+            ("priority", Some(priority)) if PRIORITIES.contains(&priority) => {
+                directives.priority = Some(priority.to_string());
+            }
+            _ => eprintln!("⚠️  Ignoring unknown verdant directive in {}: {}", name, line.trim()),
+        }
+    }
+
+    if let Region::Verbatim(lines) = region {
+        stash(lines, &mut output, &mut directives, next_id);
+    }
+
+    (output.join("\n"), directives)
+}
+
+fn stash(lines: Vec<String>, output: &mut Vec<String>, directives: &mut FileDirectives, next_id: &mut usize) {
+    *next_id += 1;
+    output.push(placeholder(*next_id));
+    directives.verbatim.push((*next_id, lines.join("\n")));
+}
+
+// Private-use characters survive every compression pass unchanged
+fn placeholder(id: usize) -> String {
+    format!("\u{E000}{}\u{E001}", id)
+}
+
+/// Compress each level region of `content` with `compress`, using
+/// `default_level` outside regions.
+pub fn compress_regions(content: &str, default_level: &str, mut compress: impl FnMut(&str, &str) -> String) -> String {
+    let mut output: Vec<String> = Vec::new();
+    let mut level = default_level.to_string();
+    let mut segment: Vec<&str> = Vec::new();
+
+    let mut flush = |segment: &mut Vec<&str>, level: &str, output: &mut Vec<String>| {
+        if !segment.is_empty() {
+            let compressed = compress(&segment.join("\n"), level);
+            if !compressed.trim().is_empty() {
+                output.push(compressed);
+            }
+            segment.clear();
+        }
+    };
+
+    for line in content.lines() {
+        match level_marker(line) {
+            Some(marker_level) => {
+                flush(&mut segment, &level, &mut output);
+                level = marker_level.unwrap_or(default_level).to_string();
+            }
+            None => segment.push(line),
+        }
+    }
+    flush(&mut segment, &level, &mut output);
+
+    output.join("\n")
+}
+
+/// A normalized level marker line: `Some(Some(level))` opens a level region,
+/// `Some(None)` ends one.
+pub fn level_marker(line: &str) -> Option<Option<&str>> {
+    if line == END_MARKER {
+        return Some(None);
+    }
+    line.strip_prefix(LEVEL_MARKER).and_then(|rest| rest.strip_suffix("-->")).map(Some)
+}
+
+/// Put verbatim regions back in place of their placeholders.
+pub fn restore_verbatim(content: &str, verbatim: &[(usize, String)]) -> String {
+    let mut result = content.to_string();
+    for (id, text) in verbatim {
+        result = result.replace(&placeholder(*id), text);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_to(content: &str) -> (String, FileDirectives) {
+        apply(content, "a.md", &mut 0)
+    }
+
+    #[test]
+    fn skip_regions_are_dropped_after_mixed_fences() {
+        let content = "~~~text\n```\n~~~\n<!-- verdant:skip -->\nsecret\n<!-- verdant:end -->\nkept";
+        let (output, directives) = apply_to(content);
+        assert_eq!(output, "~~~text\n```\n~~~\nkept");
+        assert_eq!(directives.skipped_lines, 1);
+    }
+
+    #[test]
+    fn directives_inside_code_are_plain_text() {
+        let content = "````md\n```\n<!-- verdant:skip -->\n```\n````\nafter";
+        let (output, directives) = apply_to(content);
+        assert_eq!(output, content);
+        assert_eq!(directives.skipped_lines, 0);
+    }
+
+    #[test]
+    fn verbatim_regions_round_trip() {
+        let content = "<!-- verdant:priority high -->\n<!--verdant:verbatim-->\nKeep   this\n<!-- verdant:end -->\ntext";
+        let (output, directives) = apply_to(content);
+        assert_eq!(directives.priority.as_deref(), Some("high"));
+        assert_eq!(output, format!("{}\ntext", placeholder(1)));
+        assert_eq!(restore_verbatim(&output, &directives.verbatim), "Keep   this\ntext");
+    }
+
+    #[test]
+    fn level_regions_are_compressed_at_their_level() {
+        let (output, _) = apply_to("a\n<!-- verdant:level low -->\nb\n<!-- verdant:end -->\nc");
+        let compressed = compress_regions(&output, "high", |text, level| format!("{}@{}", text, level));
+        assert_eq!(compressed, "a@high\nb@low\nc@high");
+    }
+}
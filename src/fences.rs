@@ -0,0 +1,174 @@
+// Fenced code blocks as CommonMark defines them: a run of three or more
+// backticks or tildes opens a block, and only a run of the same character at
+// least as long, with nothing after it, closes it. A ```` fence can therefore
+// show ``` examples. Passes that look inside blocks, or skip them, should
+// walk them here so they agree on where a block ends.
+
+pub struct Block<'a> {
+    pub start: usize, // Opening fence line
+    pub end: usize,   // Closing fence line, or the last line when unclosed
+    pub indent: &'a str,
+    pub marker: &'a str, // The opening run, e.g. "````"
+    pub lang: String,    // First word of the info string, lowercased
+    pub open: &'a str,
+    pub body: Vec<&'a str>,
+    pub close: Option<&'a str>,
+}
+
+/// Every fenced block in `content`, in order. An unclosed block runs to the
+/// end of the content.
+pub fn blocks(content: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut open: Option<Block> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        match &mut open {
+            Some(block) if closes(line, block.marker) => {
+                let mut block = open.take().unwrap();
+                block.end = i;
+                block.close = Some(line);
+                blocks.push(block);
+            }
+            Some(block) => {
+                block.body.push(line);
+                block.end = i;
+            }
+            None => open = opening(i, line),
+        }
+    }
+
+    blocks.extend(open);
+    blocks
+}
+
+/// Rebuild `content` with each block's opening line and body replaced by
+/// `f(block)` when it returns lines. Closing fences are kept as they are.
+pub fn map_blocks(content: &str, mut f: impl FnMut(&Block) -> Option<Vec<String>>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output: Vec<String> = Vec::new();
+    let mut next_line = 0;
+
+    for block in blocks(content) {
+        let Some(replacement) = f(&block) else { continue };
+        output.extend(lines[next_line..block.start].iter().map(|l| l.to_string()));
+        output.extend(replacement);
+        output.extend(block.close.map(str::to_string));
+        next_line = block.end + 1;
+    }
+    if next_line == 0 {
+        return content.to_string();
+    }
+
+    output.extend(lines[next_line..].iter().map(|l| l.to_string()));
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Rebuild `content` with each whole block, closing fence included, replaced
+/// by the single line `f(block)` returns, if any.
+pub fn replace_blocks(content: &str, mut f: impl FnMut(&Block) -> Option<String>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output: Vec<String> = Vec::new();
+    let mut next_line = 0;
+
+    for block in blocks(content) {
+        let Some(replacement) = f(&block) else { continue };
+        output.extend(lines[next_line..block.start].iter().map(|l| l.to_string()));
+        output.push(replacement);
+        next_line = block.end + 1;
+    }
+    if next_line == 0 {
+        return content.to_string();
+    }
+
+    output.extend(lines[next_line..].iter().map(|l| l.to_string()));
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// For each line of `content`, whether it belongs to a fenced block
+/// (fence lines included).
+pub fn code_lines(content: &str) -> Vec<bool> {
+    let mut in_code = vec![false; content.lines().count()];
+    for block in blocks(content) {
+        in_code[block.start..=block.end].fill(true);
+    }
+    in_code
+}
+
+fn opening(index: usize, line: &str) -> Option<Block<'_>> {
+    let trimmed = line.trim_start();
+    let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let run = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+    let info = &trimmed[run..];
+    // ```code``` on one line is an inline span, not a fence
+    if run < 3 || (fence_char == '`' && info.contains('`')) {
+        return None;
+    }
+
+    Some(Block {
+        start: index,
+        end: index,
+        indent: &line[..line.len() - trimmed.len()],
+        marker: &trimmed[..run],
+        lang: info.split_whitespace().next().unwrap_or("").to_lowercase(),
+        open: line,
+        body: Vec::new(),
+        close: None,
+    })
+}
+
+fn closes(line: &str, marker: &str) -> bool {
+    let fence_char = marker.chars().next().unwrap_or('`');
+    let trimmed = line.trim();
+    let run = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+    run >= marker.len() && run == trimmed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_fence_must_be_as_long_as_the_opening() {
+        let content = "````md\n```bash\necho hi\n```\n````\ntext\n~~~\n```\n~~~~\n";
+        let blocks = blocks(content);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].lang, "md");
+        assert_eq!(blocks[0].body, ["```bash", "echo hi", "```"]);
+        assert_eq!(blocks[0].close, Some("````"));
+        assert_eq!(blocks[1].body, ["```"]);
+        assert_eq!(blocks[1].close, Some("~~~~"));
+    }
+
+    #[test]
+    fn inline_spans_and_info_strings_are_not_closers() {
+        let content = "```rust``` is inline\n```js title=\"a\"\nlet a = 1;\n``` trailing\n```\n";
+        let blocks = blocks(content);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].start, 1);
+        assert_eq!(blocks[0].body, ["let a = 1;", "``` trailing"]);
+        assert_eq!(code_lines(content), [false, true, true, true, true]);
+    }
+
+    #[test]
+    fn unclosed_blocks_run_to_the_end() {
+        let content = "a\n```\ncode\n";
+        let mapped = map_blocks(content, |block| Some(vec![block.open.to_string(), block.body.join("").to_uppercase()]));
+        assert_eq!(mapped, "a\n```\nCODE\n");
+        assert_eq!(map_blocks("x\n", |_| None), "x\n");
+    }
+
+    #[test]
+    fn replaced_blocks_lose_their_closing_fence() {
+        let content = "a\n````md\n```\nx\n```\n````\nb\n";
+        assert_eq!(replace_blocks(content, |block| Some(format!("<{}>", block.body.len()))), "a\n<3>\nb\n");
+        assert_eq!(replace_blocks(content, |_| None), content);
+    }
+}
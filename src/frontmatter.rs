@@ -0,0 +1,208 @@
+// YAML (---) and TOML (+++) frontmatter blocks at the top of a document.
+// They are split off before compression so the whitespace and separator
+// passes don't mangle them, and their fields feed the file's metadata.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use yaml_rust2::{Yaml, YamlLoader};
+
+#[derive(Default, Clone)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub weight: Option<i64>,
+    pub draft: bool,
+    pub fields: Vec<(String, Vec<String>)>, // Every top-level field, for --filter
+}
+
+/// Split frontmatter off the top of `content`. Returns the parsed block (if
+/// any) and the remaining body. Malformed blocks are reported and left in
+/// place; blocks that aren't a mapping (a `---` rule above prose) are left alone.
+pub fn split(content: &str, name: &str) -> (Option<Frontmatter>, String) {
+    let content_start = content.trim_start_matches('\u{feff}');
+    let fence = if content_start.starts_with("---\n") || content_start.starts_with("---\r\n") {
+        "---"
+    } else if content_start.starts_with("+++\n") || content_start.starts_with("+++\r\n") {
+        "+++"
+    } else {
+        return (None, content.to_string());
+    };
+
+    // Find the closing fence on a line of its own
+    let after_open = &content_start[content_start.find('\n').unwrap() + 1..];
+    let mut offset = 0;
+    let mut block_end = None;
+    for line in after_open.split_inclusive('\n') {
+        if line.trim_end() == fence || (fence == "---" && line.trim_end() == "...") {
+            block_end = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let Some((block_end, body_start)) = block_end else {
+        return (None, content.to_string());
+    };
+
+    let block = &after_open[..block_end];
+    let body = after_open[body_start..].to_string();
+    let parsed = if fence == "---" { parse_yaml(block) } else { parse_toml(block) };
+
+    match parsed {
+        Ok(Some(frontmatter)) => (Some(frontmatter), body),
+        Ok(None) => (None, content.to_string()),
+        Err(e) => {
+            eprintln!("⚠️  Could not parse frontmatter in {}: {}", name, e);
+            (None, content.to_string())
+        }
+    }
+}
+
+fn parse_yaml(block: &str) -> Result<Option<Frontmatter>, String> {
+    let docs = YamlLoader::load_from_str(block).map_err(|e| e.to_string())?;
+    let Some(Yaml::Hash(map)) = docs.first() else {
+        return Ok(None);
+    };
+    let mut frontmatter = Frontmatter::default();
+
+    for (key, value) in map {
+        let Some(key) = key.as_str() else { continue };
+        let values: Vec<String> = match value {
+            Yaml::Array(items) => items.iter().filter_map(yaml_scalar).collect(),
+            // Comma-separated strings are common for tags/keywords
+            Yaml::String(s) if key == "tags" || key == "keywords" => {
+                s.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+            }
+            other => yaml_scalar(other).into_iter().collect(),
+        };
+        frontmatter.fields.push((key.to_string(), values));
+    }
+
+    apply_known_fields(&mut frontmatter);
+    Ok(Some(frontmatter))
+}
+
+fn yaml_scalar(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_toml(block: &str) -> Result<Option<Frontmatter>, String> {
+    let table: toml::Table = block.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    let mut frontmatter = Frontmatter::default();
+
+    for (key, value) in &table {
+        let values: Vec<String> = match value {
+            toml::Value::Array(items) => items.iter().filter_map(toml_scalar).collect(),
+            other => toml_scalar(other).into_iter().collect(),
+        };
+        frontmatter.fields.push((key.clone(), values));
+    }
+
+    apply_known_fields(&mut frontmatter);
+    Ok(Some(frontmatter))
+}
+
+fn toml_scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Datetime(d) => Some(d.to_string()),
+        _ => None,
+    }
+}
+
+fn apply_known_fields(frontmatter: &mut Frontmatter) {
+    let first = |key: &str| {
+        frontmatter.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.first().cloned())
+    };
+
+    let title = first("title");
+    let date = first("date").and_then(|d| parse_date(&d));
+    let weight = first("weight")
+        .or_else(|| first("sidebar_position"))
+        .or_else(|| first("nav_order"))
+        .and_then(|w| w.parse::<f64>().ok())
+        .map(|w| w as i64);
+    let draft = first("draft").is_some_and(|d| d == "true");
+    let tags = frontmatter.fields
+        .iter()
+        .filter(|(k, _)| k == "tags" || k == "keywords" || k == "categories")
+        .flat_map(|(_, v)| v.iter().cloned())
+        .collect();
+
+    frontmatter.title = title;
+    frontmatter.date = date;
+    frontmatter.weight = weight;
+    frontmatter.draft = draft;
+    frontmatter.tags = tags;
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+/// Check a `key=value` / `key!=value` filter against the frontmatter. List
+/// fields match when any element does; files without the field only pass
+/// `!=` filters.
+pub fn matches_filter(frontmatter: Option<&Frontmatter>, filter: &str) -> Result<bool, String> {
+    let (key, value, negate) = if let Some((key, value)) = filter.split_once("!=") {
+        (key, value, true)
+    } else if let Some((key, value)) = filter.split_once('=') {
+        (key, value, false)
+    } else {
+        return Err(format!("invalid filter '{}' (expected key=value or key!=value)", filter));
+    };
+
+    let found = frontmatter
+        .and_then(|fm| fm.fields.iter().find(|(k, _)| k == key.trim()))
+        .is_some_and(|(_, values)| values.iter().any(|v| v == value.trim()));
+
+    Ok(found != negate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappings_are_split_off() {
+        let (frontmatter, body) = split("---\ntitle: Intro\ntags: a, b\n---\n# Intro\n", "a.md");
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Intro"));
+        assert_eq!(frontmatter.tags, ["a", "b"]);
+        assert_eq!(body, "# Intro\n");
+
+        let (frontmatter, body) = split("+++\ntitle = \"Intro\"\nweight = 3\n+++\nBody\n", "a.md");
+        assert_eq!(frontmatter.unwrap().weight, Some(3));
+        assert_eq!(body, "Body\n");
+    }
+
+    #[test]
+    fn rules_around_prose_are_not_frontmatter() {
+        for content in ["---\nJust a rule above prose.\n---\nBody\n", "---\n- a list\n---\nBody\n", "---\n---\nBody\n"] {
+            let (frontmatter, body) = split(content, "a.md");
+            assert!(frontmatter.is_none());
+            assert_eq!(body, content);
+        }
+    }
+}
@@ -0,0 +1,884 @@
+// Minimal read-only access to a local git repository, used to date files by
+// their commit history instead of filesystem mtimes (which a fresh clone or CI
+// checkout resets). Reads loose objects and packfiles straight from .git.
+
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type ObjectId = [u8; 20];
+type PackedObject = (u8, std::rc::Rc<Vec<u8>>);
+
+pub struct FileHistory {
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    pub authors: Vec<String>, // Ordered by first contribution
+    pub commits: usize,
+}
+
+pub enum FileChange {
+    Added,
+    Modified { previous: String },
+}
+
+struct Commit {
+    tree: ObjectId,
+    parents: Vec<ObjectId>,
+    author: String,
+    time: i64,
+}
+
+struct TreeEntry {
+    name: Vec<u8>,
+    is_tree: bool,
+    id: ObjectId,
+}
+
+struct Pack {
+    index: Vec<u8>,
+    data: Vec<u8>,
+    count: usize,
+}
+
+pub struct Repository {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    packs: Vec<Pack>,
+    trees: HashMap<ObjectId, std::rc::Rc<Vec<TreeEntry>>>,
+    delta_bases: HashMap<(usize, usize), PackedObject>,
+}
+
+/// Collect commit history for every path that lives inside a git work tree.
+/// Paths outside any repository are simply absent from the result.
+pub fn file_histories(paths: &[PathBuf]) -> HashMap<PathBuf, FileHistory> {
+    let mut by_repo: HashMap<PathBuf, Vec<(PathBuf, String)>> = HashMap::new();
+
+    for path in paths {
+        let Ok(absolute) = fs::canonicalize(path) else { continue };
+        let Some(workdir) = find_workdir(&absolute) else { continue };
+        if let Ok(relative) = absolute.strip_prefix(&workdir) {
+            let repo_path = relative.to_string_lossy().replace('\\', "/");
+            by_repo.entry(workdir).or_default().push((path.clone(), repo_path));
+        }
+    }
+
+    let mut histories = HashMap::new();
+    for (workdir, files) in by_repo {
+        match Repository::open(&workdir) {
+            Ok(mut repo) => {
+                let repo_paths: Vec<&str> = files.iter().map(|(_, p)| p.as_str()).collect();
+                let mut found = repo.histories(&repo_paths);
+                for (path, repo_path) in &files {
+                    if let Some(history) = found.remove(repo_path.as_str()) {
+                        histories.insert(path.clone(), history);
+                    }
+                }
+            }
+            Err(e) => eprintln!("⚠️  Could not read git history in {}: {}", workdir.display(), e),
+        }
+    }
+
+    histories
+}
+
+/// Compare `paths` against their content at the merge base of `revision` and
+/// HEAD, like `git diff <revision>...`, but including uncommitted edits.
+/// Unchanged files are absent from the result.
+pub fn changes_since(paths: &[PathBuf], revision: &str) -> Result<HashMap<PathBuf, FileChange>, String> {
+    let mut by_repo: HashMap<PathBuf, Vec<(PathBuf, String)>> = HashMap::new();
+
+    for path in paths {
+        let absolute = fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let workdir = find_workdir(&absolute)
+            .ok_or_else(|| format!("{} is not inside a git repository", path.display()))?;
+        if let Ok(relative) = absolute.strip_prefix(&workdir) {
+            let repo_path = relative.to_string_lossy().replace('\\', "/");
+            by_repo.entry(workdir).or_default().push((path.clone(), repo_path));
+        }
+    }
+
+    let mut changes = HashMap::new();
+    for (workdir, files) in by_repo {
+        let mut repo = Repository::open(&workdir)?;
+        let target = repo
+            .resolve_revision(revision)
+            .ok_or_else(|| format!("unknown revision '{}' in {}", revision, workdir.display()))?;
+        let base = match repo.resolve_head() {
+            Some(head) => repo.merge_base(target, head).unwrap_or(target),
+            None => target,
+        };
+        let base_tree = repo
+            .read_commit(&base)
+            .ok_or_else(|| format!("could not read commit for '{}'", revision))?
+            .tree;
+
+        for (path, repo_path) in files {
+            let current = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            match repo.lookup_path(&base_tree, &repo_path) {
+                None => {
+                    changes.insert(path, FileChange::Added);
+                }
+                Some(blob) => {
+                    let (_, previous) = repo.read_object(&blob).ok_or("missing blob in object store")?;
+                    if previous != current {
+                        let previous = String::from_utf8_lossy(&previous).to_string();
+                        changes.insert(path, FileChange::Modified { previous });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Compact line diff: one `@<line>` marker per hunk (line number in the new
+/// text) followed by `-` and `+` lines, without context.
+pub fn compact_diff(old: &str, new: &str) -> Vec<String> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Strip the common prefix and suffix so the quadratic part stays small
+    let prefix = old_lines.iter().zip(&new_lines).take_while(|(a, b)| a == b).count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+    // Longest common subsequence table; huge rewrites are shown as one hunk
+    let (n, m) = (old_mid.len(), new_mid.len());
+    let mut lcs = vec![0u32; if n * m <= 4_000_000 { (n + 1) * (m + 1) } else { 0 }];
+    if !lcs.is_empty() {
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut in_hunk = false;
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            in_hunk = false;
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if !in_hunk {
+            output.push(format!("@{}", prefix + j + 1));
+            in_hunk = true;
+        }
+        let take_old = j >= m || (i < n && !lcs.is_empty() && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1])
+            || (i < n && lcs.is_empty());
+        if take_old {
+            output.push(format!("-{}", old_mid[i]));
+            i += 1;
+        } else {
+            output.push(format!("+{}", new_mid[j]));
+            j += 1;
+        }
+    }
+
+    output
+}
+
+fn find_workdir(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(|dir| dir.to_path_buf())
+}
+
+impl Repository {
+    pub fn open(workdir: &Path) -> Result<Repository, String> {
+        let dot_git = workdir.join(".git");
+
+        // Worktrees and submodules use a ".git" file pointing at the real git dir
+        let git_dir = if dot_git.is_file() {
+            let pointer = fs::read_to_string(&dot_git).map_err(|e| e.to_string())?;
+            let target = pointer
+                .trim()
+                .strip_prefix("gitdir:")
+                .ok_or("malformed .git file")?
+                .trim();
+            workdir.join(target)
+        } else {
+            dot_git
+        };
+
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(dir) => git_dir.join(dir.trim()),
+            Err(_) => git_dir.clone(),
+        };
+
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(common_dir.join("objects/pack")) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let idx_path = entry.path();
+                if idx_path.extension().is_some_and(|ext| ext == "idx") {
+                    let index = fs::read(&idx_path).map_err(|e| e.to_string())?;
+                    let data = fs::read(idx_path.with_extension("pack")).map_err(|e| e.to_string())?;
+                    // Only version 2 indexes are written by any git from the last decade
+                    if index.len() < 8 + 256 * 4 || index[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+                        continue;
+                    }
+                    let count = read_u32(&index, 8 + 255 * 4) as usize;
+                    packs.push(Pack { index, data, count });
+                }
+            }
+        }
+
+        Ok(Repository {
+            git_dir,
+            common_dir,
+            packs,
+            trees: HashMap::new(),
+            delta_bases: HashMap::new(),
+        })
+    }
+
+    /// Walk every commit reachable from HEAD and record which of `paths`
+    /// (relative to the work tree, `/`-separated) each commit touched.
+    pub fn histories(&mut self, paths: &[&str]) -> HashMap<String, FileHistory> {
+        let mut histories: HashMap<String, FileHistory> = HashMap::new();
+        let Some(head) = self.resolve_head() else {
+            return histories;
+        };
+
+        let mut queue = VecDeque::from([head]);
+        let mut visited = HashSet::from([head]);
+        let mut author_times: HashMap<(String, String), i64> = HashMap::new();
+
+        while let Some(id) = queue.pop_front() {
+            // Shallow clones simply end the walk where objects run out
+            let Some(commit) = self.read_commit(&id) else { continue };
+
+            let parent_trees: Vec<ObjectId> = commit.parents
+                .iter()
+                .filter_map(|p| self.read_commit(p).map(|c| c.tree))
+                .collect();
+
+            for path in paths {
+                let current = self.lookup_path(&commit.tree, path);
+                let Some(current) = current else { continue };
+
+                // A merge only counts when the file differs from every parent
+                let touched = parent_trees
+                    .iter()
+                    .all(|tree| self.lookup_path(tree, path) != Some(current));
+
+                if touched {
+                    let time = DateTime::<Utc>::from_timestamp(commit.time, 0).unwrap_or_default();
+                    let history = histories.entry(path.to_string()).or_insert(FileHistory {
+                        created: time,
+                        modified: time,
+                        authors: Vec::new(),
+                        commits: 0,
+                    });
+                    history.created = history.created.min(time);
+                    history.modified = history.modified.max(time);
+                    history.commits += 1;
+
+                    let first_seen = author_times.entry((path.to_string(), commit.author.clone())).or_insert(commit.time);
+                    *first_seen = (*first_seen).min(commit.time);
+                    if !history.authors.contains(&commit.author) {
+                        history.authors.push(commit.author.clone());
+                    }
+                }
+            }
+
+            for parent in commit.parents {
+                if visited.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+
+        // The walk isn't strictly chronological across merges, so order authors afterwards
+        for (path, history) in histories.iter_mut() {
+            history.authors.sort_by_key(|author| author_times.get(&(path.clone(), author.clone())).copied());
+        }
+
+        histories
+    }
+
+    /// Accepts HEAD, branch/tag/remote names, full or abbreviated ids, and
+    /// `~N` / `^N` suffixes.
+    fn resolve_revision(&mut self, revision: &str) -> Option<ObjectId> {
+        let split = revision.find(['~', '^']).unwrap_or(revision.len());
+        let (name, mut suffix) = revision.split_at(split);
+
+        let mut id = if name == "HEAD" {
+            self.resolve_head()?
+        } else {
+            ["", "refs/", "refs/heads/", "refs/tags/", "refs/remotes/"]
+                .iter()
+                .find_map(|prefix| self.resolve_ref(&format!("{}{}", prefix, name)))
+                .or_else(|| self.find_abbreviated(name))?
+        };
+        id = self.peel_to_commit(id)?;
+
+        while let Some(op) = suffix.chars().next() {
+            suffix = &suffix[1..];
+            let digits = suffix.chars().take_while(|c| c.is_ascii_digit()).count();
+            let count: usize = if digits == 0 { 1 } else { suffix[..digits].parse().ok()? };
+            suffix = &suffix[digits..];
+
+            if op == '~' {
+                for _ in 0..count {
+                    id = *self.read_commit(&id)?.parents.first()?;
+                }
+            } else if count > 0 {
+                id = *self.read_commit(&id)?.parents.get(count - 1)?;
+            }
+        }
+
+        Some(id)
+    }
+
+    fn peel_to_commit(&mut self, id: ObjectId) -> Option<ObjectId> {
+        let (kind, data) = self.read_object(&id)?;
+        match kind {
+            1 => Some(id),
+            // Annotated tags point at their target on the first line
+            4 => {
+                let text = String::from_utf8_lossy(&data);
+                let target = parse_hex(text.lines().next()?.strip_prefix("object ")?)?;
+                self.peel_to_commit(target)
+            }
+            _ => None,
+        }
+    }
+
+    fn find_abbreviated(&self, prefix: &str) -> Option<ObjectId> {
+        if prefix.len() < 4 || prefix.len() > 40 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let prefix = prefix.to_lowercase();
+        let mut matches = HashSet::new();
+
+        if let Ok(entries) = fs::read_dir(self.common_dir.join("objects").join(&prefix[..2])) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let hex = format!("{}{}", &prefix[..2], entry.file_name().to_string_lossy());
+                if hex.starts_with(&prefix) {
+                    matches.extend(parse_hex(&hex));
+                }
+            }
+        }
+
+        for pack in &self.packs {
+            let ids_start = 8 + 256 * 4;
+            for position in 0..pack.count {
+                let Some(bytes) = pack.index.get(ids_start + position * 20..ids_start + position * 20 + 20) else { break };
+                let id: ObjectId = bytes.try_into().ok()?;
+                if to_hex(&id).starts_with(&prefix) {
+                    matches.insert(id);
+                }
+            }
+        }
+
+        // Ambiguous prefixes are rejected rather than guessed
+        if matches.len() == 1 { matches.into_iter().next() } else { None }
+    }
+
+    /// A best common ancestor of `a` and `b`: a commit reachable from both
+    /// that no other common ancestor descends from, as `git merge-base` picks.
+    fn merge_base(&mut self, a: ObjectId, b: ObjectId) -> Option<ObjectId> {
+        let from_a = self.ancestors([a]);
+        let from_b = self.ancestors([b]);
+
+        // Walk b's history in order so the choice among several bases is stable
+        let common: Vec<ObjectId> = from_b.into_iter().filter(|id| from_a.contains(id)).collect();
+        let parents: Vec<ObjectId> = common
+            .iter()
+            .flat_map(|id| self.read_commit(id).map(|c| c.parents).unwrap_or_default())
+            .collect();
+        let below_common: HashSet<ObjectId> = self.ancestors(parents).into_iter().collect();
+
+        common.into_iter().find(|id| !below_common.contains(id))
+    }
+
+    /// `starts` and every commit reachable from them, in breadth-first order.
+    fn ancestors(&mut self, starts: impl IntoIterator<Item = ObjectId>) -> Vec<ObjectId> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue: VecDeque<ObjectId> = starts.into_iter().filter(|id| visited.insert(*id)).collect();
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for parent in self.read_commit(&id).map(|c| c.parents).unwrap_or_default() {
+                if visited.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        order
+    }
+
+    fn resolve_head(&self) -> Option<ObjectId> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        match head.strip_prefix("ref:") {
+            Some(name) => self.resolve_ref(name.trim()),
+            None => parse_hex(head),
+        }
+    }
+
+    fn resolve_ref(&self, name: &str) -> Option<ObjectId> {
+        for dir in [&self.git_dir, &self.common_dir] {
+            if let Ok(value) = fs::read_to_string(dir.join(name)) {
+                let value = value.trim();
+                return match value.strip_prefix("ref:") {
+                    Some(target) => self.resolve_ref(target.trim()),
+                    None => parse_hex(value),
+                };
+            }
+        }
+
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .find_map(|line| {
+                let (id, ref_name) = line.split_once(' ')?;
+                if ref_name == name { parse_hex(id) } else { None }
+            })
+    }
+
+    fn read_commit(&mut self, id: &ObjectId) -> Option<Commit> {
+        let (kind, data) = self.read_object(id)?;
+        if kind != 1 {
+            return None;
+        }
+
+        let text = String::from_utf8_lossy(&data);
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = String::new();
+        let mut time = 0;
+
+        for line in text.lines() {
+            if line.is_empty() {
+                break; // Headers end at the first blank line
+            } else if let Some(value) = line.strip_prefix("tree ") {
+                tree = parse_hex(value);
+            } else if let Some(value) = line.strip_prefix("parent ") {
+                parents.extend(parse_hex(value));
+            } else if let Some(value) = line.strip_prefix("author ") {
+                // "Name <email> 1700000000 +0100"
+                if let Some((name, rest)) = value.split_once(" <") {
+                    author = name.to_string();
+                    time = rest
+                        .rsplit(' ')
+                        .nth(1)
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or(0);
+                }
+            }
+        }
+
+        Some(Commit { tree: tree?, parents, author, time })
+    }
+
+    fn lookup_path(&mut self, tree: &ObjectId, path: &str) -> Option<ObjectId> {
+        let mut current = *tree;
+        let mut components = path.split('/').peekable();
+
+        while let Some(component) = components.next() {
+            let entries = self.read_tree(&current)?;
+            let entry = entries.iter().find(|e| e.name == component.as_bytes())?;
+            if components.peek().is_some() {
+                if !entry.is_tree {
+                    return None;
+                }
+                current = entry.id;
+            } else {
+                return if entry.is_tree { None } else { Some(entry.id) };
+            }
+        }
+
+        None
+    }
+
+    fn read_tree(&mut self, id: &ObjectId) -> Option<std::rc::Rc<Vec<TreeEntry>>> {
+        if let Some(entries) = self.trees.get(id) {
+            return Some(entries.clone());
+        }
+
+        let (kind, data) = self.read_object(id)?;
+        if kind != 2 {
+            return None;
+        }
+
+        // Entries are "<mode> <name>\0<20-byte id>" back to back
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let space = pos + data[pos..].iter().position(|&b| b == b' ')?;
+            let nul = space + data[space..].iter().position(|&b| b == 0)?;
+            let id: ObjectId = data.get(nul + 1..nul + 21)?.try_into().ok()?;
+            entries.push(TreeEntry {
+                is_tree: &data[pos..space] == b"40000",
+                name: data[space + 1..nul].to_vec(),
+                id,
+            });
+            pos = nul + 21;
+        }
+
+        let entries = std::rc::Rc::new(entries);
+        self.trees.insert(*id, entries.clone());
+        Some(entries)
+    }
+
+    /// Returns the object type (1 commit, 2 tree, 3 blob, 4 tag) and its body.
+    fn read_object(&mut self, id: &ObjectId) -> Option<(u8, Vec<u8>)> {
+        let hex = to_hex(id);
+        let loose = self.common_dir.join("objects").join(&hex[..2]).join(&hex[2..]);
+        if let Ok(compressed) = fs::read(&loose) {
+            let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed).ok()?;
+            let nul = raw.iter().position(|&b| b == 0)?;
+            let kind = match raw[..nul].split(|&b| b == b' ').next()? {
+                b"commit" => 1,
+                b"tree" => 2,
+                b"blob" => 3,
+                b"tag" => 4,
+                _ => return None,
+            };
+            return Some((kind, raw[nul + 1..].to_vec()));
+        }
+
+        for pack in 0..self.packs.len() {
+            if let Some(offset) = self.packs[pack].find(id) {
+                return self.read_packed(pack, offset).map(|(kind, data)| (kind, data.to_vec()));
+            }
+        }
+
+        None
+    }
+
+    fn read_packed(&mut self, pack: usize, offset: usize) -> Option<PackedObject> {
+        if let Some((kind, data)) = self.delta_bases.get(&(pack, offset)) {
+            return Some((*kind, data.clone()));
+        }
+
+        let data = &self.packs[pack].data;
+        let mut pos = offset;
+        let mut byte = *data.get(pos)?;
+        pos += 1;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = *data.get(pos)?;
+            pos += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let result = match kind {
+            1..=4 => {
+                let body = inflate(data.get(pos..)?, size)?;
+                (kind, std::rc::Rc::new(body))
+            }
+            6 => {
+                // OFS_DELTA: base is an earlier object in the same pack
+                let mut byte = *data.get(pos)?;
+                pos += 1;
+                let mut distance = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    byte = *data.get(pos)?;
+                    pos += 1;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+                }
+                let delta = inflate(data.get(pos..)?, size)?;
+                let (base_kind, base) = self.read_packed(pack, offset.checked_sub(distance)?)?;
+                (base_kind, std::rc::Rc::new(apply_delta(&base, &delta)?))
+            }
+            7 => {
+                // REF_DELTA: base is named by id and may live anywhere
+                let base_id: ObjectId = data.get(pos..pos + 20)?.try_into().ok()?;
+                let delta = inflate(data.get(pos + 20..)?, size)?;
+                let (base_kind, base) = self.read_object(&base_id)?;
+                (base_kind, std::rc::Rc::new(apply_delta(&base, &delta)?))
+            }
+            _ => return None,
+        };
+
+        // Bases are shared by long delta chains; keep the cache bounded
+        if self.delta_bases.len() > 4096 {
+            self.delta_bases.clear();
+        }
+        self.delta_bases.insert((pack, offset), result.clone());
+        Some(result)
+    }
+}
+
+impl Pack {
+    fn find(&self, id: &ObjectId) -> Option<usize> {
+        // Fan-out table narrows the search to ids sharing the first byte
+        let first = id[0] as usize;
+        let start = if first == 0 { 0 } else { read_u32(&self.index, 8 + (first - 1) * 4) as usize };
+        let end = read_u32(&self.index, 8 + first * 4) as usize;
+        let ids_start = 8 + 256 * 4;
+
+        let (mut low, mut high) = (start, end);
+        while low < high {
+            let mid = (low + high) / 2;
+            let candidate = self.index.get(ids_start + mid * 20..ids_start + mid * 20 + 20)?;
+            match candidate.cmp(id.as_slice()) {
+                std::cmp::Ordering::Equal => return Some(self.offset_at(mid)),
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+
+        None
+    }
+
+    fn offset_at(&self, position: usize) -> usize {
+        let offsets_start = 8 + 256 * 4 + self.count * 24;
+        let offset = read_u32(&self.index, offsets_start + position * 4);
+        if offset & 0x8000_0000 == 0 {
+            return offset as usize;
+        }
+
+        // Packs over 2GB store large offsets in a separate 64-bit table
+        let large_start = offsets_start + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+        let high = read_u32(&self.index, large_start) as usize;
+        let low = read_u32(&self.index, large_start + 4) as usize;
+        (high << 32) | low
+    }
+}
+
+fn inflate(compressed: &[u8], size: usize) -> Option<Vec<u8>> {
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, size + 1).ok()
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let _base_size = read_varint(delta, &mut pos)?;
+    let result_size = read_varint(delta, &mut pos)?;
+    let mut result = Vec::with_capacity(result_size);
+
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // Copy a range of the base; bits say which offset/size bytes follow
+            let mut copy_offset = 0usize;
+            let mut copy_size = 0usize;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    copy_offset |= (*delta.get(pos)? as usize) << (i * 8);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    copy_size |= (*delta.get(pos)? as usize) << (i * 8);
+                    pos += 1;
+                }
+            }
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+            result.extend_from_slice(base.get(copy_offset..copy_offset + copy_size)?);
+        } else if op != 0 {
+            // Insert the next `op` bytes literally
+            result.extend_from_slice(delta.get(pos..pos + op as usize)?);
+            pos += op as usize;
+        } else {
+            return None;
+        }
+    }
+
+    Some(result)
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    data.get(pos..pos + 4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn parse_hex(hex: &str) -> Option<ObjectId> {
+    let hex = hex.trim();
+    if hex.len() != 40 {
+        return None;
+    }
+
+    let mut id = [0u8; 20];
+    for (i, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(id)
+}
+
+fn to_hex(id: &ObjectId) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::process::Command;
+
+    // A scratch repository built with the git CLI
+    struct Fixture(TempDir);
+
+    impl Fixture {
+        fn new(name: &str) -> Fixture {
+            let fixture = Fixture(TempDir::new(&format!("git-{}", name)));
+            fixture.git(&["init", "-q", "-b", "main"]);
+            fixture
+        }
+
+        fn git(&self, args: &[&str]) -> String {
+            self.git_at(args, "2024-01-01T00:00:00Z")
+        }
+
+        // Run git with a fixed author and both dates set to `date`
+        fn git_at(&self, args: &[&str], date: &str) -> String {
+            let output = Command::new("git")
+                .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com", "-c", "commit.gpgsign=false"])
+                .args(args)
+                .current_dir(&self.0)
+                .env("GIT_CONFIG_GLOBAL", "/dev/null")
+                .env("GIT_CONFIG_NOSYSTEM", "1")
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .output()
+                .expect("git is installed");
+            assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+
+        fn commit(&self, file: &str, content: &str, date: &str) -> String {
+            fs::write(self.0.join(file), content).unwrap();
+            self.git_at(&["add", file], date);
+            self.git_at(&["commit", "-q", "-m", file], date);
+            self.git(&["rev-parse", "HEAD"])
+        }
+
+        fn open(&self) -> Repository {
+            Repository::open(&self.0).unwrap()
+        }
+    }
+
+    fn id(hex: &str) -> ObjectId {
+        parse_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn merge_base_ignores_author_dates() {
+        // main: F - X (back-dated); feature: F - f1 - merge(X) - f2.
+        // Walking back from feature meets both F and X; X is the base even
+        // though F has the newer date.
+        let repo = Fixture::new("merge-base");
+        repo.commit("a.md", "fork\n", "2024-06-01T00:00:00Z");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.commit("b.md", "feature\n", "2024-06-02T00:00:00Z");
+        repo.git(&["checkout", "-q", "main"]);
+        let x = repo.commit("c.md", "back-dated\n", "2001-01-01T00:00:00Z");
+        repo.git(&["checkout", "-q", "feature"]);
+        repo.git_at(&["merge", "-q", "--no-edit", "main"], "2024-06-03T00:00:00Z");
+        let f2 = repo.commit("b.md", "feature 2\n", "2024-06-04T00:00:00Z");
+
+        let expected = repo.git(&["merge-base", &x, &f2]);
+        assert_eq!(expected, x);
+        assert_eq!(repo.open().merge_base(id(&x), id(&f2)).map(|b| to_hex(&b)), Some(expected));
+    }
+
+    #[test]
+    fn merge_base_of_diverged_branches_is_the_fork_point() {
+        let repo = Fixture::new("diverged");
+        repo.commit("a.md", "one\n", "2024-01-01T00:00:00Z");
+        let fork = repo.commit("a.md", "two\n", "2024-01-02T00:00:00Z");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        let feature = repo.commit("b.md", "feature\n", "2024-01-03T00:00:00Z");
+        repo.git(&["checkout", "-q", "main"]);
+        let main = repo.commit("a.md", "three\n", "2024-01-04T00:00:00Z");
+
+        let mut git = repo.open();
+        assert_eq!(git.merge_base(id(&main), id(&feature)), Some(id(&fork)));
+        assert_eq!(git.merge_base(id(&feature), id(&main)), Some(id(&fork)));
+        assert_eq!(git.merge_base(id(&fork), id(&main)), Some(id(&fork)));
+    }
+
+    #[test]
+    fn reads_deltified_objects_from_packs() {
+        let repo = Fixture::new("pack");
+        let mut text: String = (0..200).map(|i| format!("line {} of a long document\n", i)).collect();
+        let mut versions = Vec::new();
+        for i in 0..5 {
+            text.push_str(&format!("addendum {}\n", i));
+            versions.push(text.clone());
+            repo.commit("doc.md", &text, &format!("2024-01-0{}T00:00:00Z", i + 1));
+        }
+        repo.git(&["repack", "-q", "-a", "-d", "-f", "--depth=50", "--window=50"]);
+        repo.git(&["prune-packed"]);
+        repo.git(&["pack-refs", "--all"]);
+
+        let pack = fs::read_dir(repo.0.join(".git/objects/pack"))
+            .unwrap()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .find(|p| p.extension().is_some_and(|x| x == "idx"))
+            .unwrap();
+        let verify = repo.git(&["verify-pack", "-v", pack.to_str().unwrap()]);
+        assert!(verify.contains("chain length"), "fixture pack has no deltas");
+
+        let mut git = repo.open();
+        for (back, expected) in versions.iter().rev().enumerate() {
+            let commit = git.resolve_revision(&format!("main~{}", back)).unwrap();
+            let tree = git.read_commit(&commit).unwrap().tree;
+            let blob = git.lookup_path(&tree, "doc.md").unwrap();
+            let (_, content) = git.read_object(&blob).unwrap();
+            assert_eq!(String::from_utf8(content).unwrap(), *expected);
+        }
+
+        let history = git.histories(&["doc.md"]).remove("doc.md").unwrap();
+        assert_eq!(history.commits, 5);
+        assert_eq!(history.authors, ["Ada"]);
+    }
+
+    #[test]
+    fn changes_since_compares_against_the_merge_base() {
+        let repo = Fixture::new("since");
+        repo.commit("a.md", "base\n", "2024-01-01T00:00:00Z");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.commit("a.md", "feature edit\n", "2024-01-02T00:00:00Z");
+        repo.commit("b.md", "new\n", "2024-01-03T00:00:00Z");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.commit("a.md", "main edit\n", "2024-01-04T00:00:00Z");
+        repo.git(&["checkout", "-q", "feature"]);
+        repo.git(&["gc", "-q"]);
+
+        let paths = [repo.0.join("a.md"), repo.0.join("b.md")];
+        let changes = changes_since(&paths, "main").unwrap();
+        assert!(matches!(&changes[&paths[0]], FileChange::Modified { previous } if previous == "base\n"));
+        assert!(matches!(changes[&paths[1]], FileChange::Added));
+    }
+}
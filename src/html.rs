@@ -0,0 +1,176 @@
+// Inline HTML and image embeds. READMEs lean on `<div align>`, `<br>`,
+// `<details>` and screenshots, none of which a model can use as-is: tags are
+// stripped down to their text, `<details><summary>` becomes a heading, and
+// images shrink to `IMG:alt` (or vanish).
+
+use crate::fences;
+use crate::links::outside_code_spans;
+use regex::{Captures, Regex};
+
+const PRESENTATIONAL_TAGS: &str = "div|span|p|center|font|b|strong|i|em|u|s|sub|sup|small|big|kbd|picture|source|a|hr|br|details|summary|img";
+
+#[derive(Default)]
+pub struct HtmlStats {
+    pub tags_stripped: usize,
+    pub comments_removed: usize,
+    pub details_converted: usize,
+    pub images_converted: usize,
+    pub images_dropped: usize,
+}
+
+/// Rewrite HTML and images outside code fences. `html_mode` is strip or
+/// keep, `image_mode` is alt, drop or keep.
+pub fn compress_html(content: &str, html_mode: &str, image_mode: &str, stats: &mut HtmlStats) -> String {
+    let re_comment = Regex::new(r"<!--([\s\S]*?)-->").unwrap();
+    let re_linked_image = Regex::new(r"\[(!\[[^\]]*\]\([^)]*\))\]\([^)]*\)").unwrap();
+    let re_md_image = Regex::new(r"!\[([^\]]*)\]\([^)]*\)").unwrap();
+    let re_img_tag = Regex::new(r"(?i)<img\b[^>]*>").unwrap();
+    let re_alt = Regex::new(r#"(?i)\balt\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    let re_summary = Regex::new(r"(?i)<summary\b[^>]*>(.*?)</summary>").unwrap();
+    let re_anchor = Regex::new(r#"(?i)<a\b[^>]*\bhref\s*=\s*"([^"]*)"[^>]*>(.*?)</a>"#).unwrap();
+    let re_code = Regex::new(r"(?i)<code>(.*?)</code>").unwrap();
+    let re_tag = Regex::new(&format!(r"(?i)</?(?:{})\b[^>]*>", PRESENTATIONAL_TAGS)).unwrap();
+    let re_heading = Regex::new(r"^(#{1,6})\s").unwrap();
+
+    map_prose_blocks(content, |block| {
+        let mut block = block.to_string();
+
+        // Comments go first so commented-out markup isn't rewritten;
+        // verdant directives stay for the compression pipeline
+        if html_mode == "strip" {
+            block = re_comment.replace_all(&block, |caps: &Captures| {
+                if caps[1].trim_start().starts_with("verdant:") {
+                    caps[0].to_string()
+                } else {
+                    stats.comments_removed += 1;
+                    String::new()
+                }
+            }).to_string();
+        }
+
+        let mut output = Vec::new();
+        let mut heading_level = 2; // Level of the section a <details> sits in
+        for line in block.split('\n') {
+            if let Some(caps) = re_heading.captures(line) {
+                heading_level = caps[1].len();
+            }
+
+            let rewritten = outside_code_spans(line, |text| {
+                let mut text = text.to_string();
+
+                if image_mode != "keep" {
+                    text = re_linked_image.replace_all(&text, "$1").to_string();
+                    text = re_md_image.replace_all(&text, |caps: &Captures| image(&caps[1], image_mode, stats)).to_string();
+                    text = re_img_tag.replace_all(&text, |caps: &Captures| {
+                        let alt = re_alt
+                            .captures(&caps[0])
+                            .and_then(|alt| alt.get(1).or(alt.get(2)))
+                            .map_or("", |m| m.as_str());
+                        image(alt, image_mode, stats)
+                    }).to_string();
+                }
+
+                if html_mode == "strip" {
+                    text = re_summary.replace_all(&text, |caps: &Captures| {
+                        stats.details_converted += 1;
+                        let title = re_tag.replace_all(&caps[1], "");
+                        format!("\n{} {}\n", "#".repeat((heading_level + 1).min(6)), title.trim())
+                    }).to_string();
+                    text = re_anchor.replace_all(&text, "[$2]($1)").to_string();
+                    text = re_code.replace_all(&text, "`$1`").to_string();
+                    text = re_tag.replace_all(&text, |caps: &Captures| {
+                        stats.tags_stripped += 1;
+                        if caps[0].to_lowercase().starts_with("<br") { " " } else { "" }
+                    }).to_string();
+                }
+
+                text
+            });
+            // Indentation inside HTML blocks isn't markdown indentation
+            if html_mode == "strip" && line.trim_start().starts_with('<') {
+                output.push(rewritten.trim_start().to_string());
+            } else {
+                output.push(rewritten);
+            }
+        }
+        output.join("\n")
+    })
+}
+
+fn image(alt: &str, image_mode: &str, stats: &mut HtmlStats) -> String {
+    let alt = alt.trim();
+    if image_mode == "drop" || alt.is_empty() {
+        stats.images_dropped += 1;
+        String::new()
+    } else {
+        stats.images_converted += 1;
+        format!("IMG:{}", alt)
+    }
+}
+
+/// Apply `f` to each run of lines outside fenced code blocks, so multi-line
+/// constructs like comments can be matched as a whole.
+fn map_prose_blocks(content: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut output: Vec<String> = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    for (line, in_code) in content.lines().zip(fences::code_lines(content)) {
+        if in_code {
+            if !prose.is_empty() {
+                output.push(f(&prose.join("\n")));
+                prose.clear();
+            }
+            output.push(line.to_string());
+        } else {
+            prose.push(line);
+        }
+    }
+    if !prose.is_empty() {
+        output.push(f(&prose.join("\n")));
+    }
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(content: &str, html_mode: &str, image_mode: &str) -> String {
+        compress_html(content, html_mode, image_mode, &mut HtmlStats::default())
+    }
+
+    #[test]
+    fn presentational_tags_keep_their_text() {
+        let content = "<div align=\"center\">\n  <b>Fast</b> markdown<br>for <a href=\"https://x.dev\">models</a>\n</div>\n<!-- todo --><!-- verdant:skip -->\n";
+        assert_eq!(
+            compress(content, "strip", "keep"),
+            "\nFast markdown for [models](https://x.dev)\n\n<!-- verdant:skip -->\n"
+        );
+    }
+
+    #[test]
+    fn details_summary_becomes_a_subheading() {
+        let content = "### Setup\n<details><summary><b>Linux</b></summary>\n\nRun it.\n</details>\n";
+        let mut stats = HtmlStats::default();
+        let output = compress_html(content, "strip", "keep", &mut stats);
+        assert_eq!(output, "### Setup\n#### Linux\n\n\nRun it.\n\n");
+        assert_eq!(stats.details_converted, 1);
+    }
+
+    #[test]
+    fn images_shrink_to_alt_text() {
+        let content = "[![Logo](logo.png)](https://x.dev) ![](spacer.gif) <img src=\"a.png\" alt='Architecture'>\n";
+        assert_eq!(compress(content, "keep", "alt"), "IMG:Logo  IMG:Architecture\n");
+        assert_eq!(compress(content, "keep", "drop"), "  \n");
+        assert_eq!(compress(content, "keep", "keep"), content);
+    }
+
+    #[test]
+    fn code_is_left_alone() {
+        let content = "Use `<br>` here.\n\n```html\n<div><b>x</b></div>\n```\n";
+        assert_eq!(compress(content, "strip", "alt"), content);
+    }
+}
@@ -0,0 +1,262 @@
+// Link compression across the bundle: README badges are dropped, tracking
+// query parameters stripped, and URLs used more than once replaced by short
+// reference ids listed once in a LINKS:{L1=...} header.
+
+use crate::SourceFile;
+use crate::fences;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+const BADGE_HOSTS: [&str; 8] = [
+    "img.shields.io",
+    "shields.io",
+    "badgen.net",
+    "badge.fury.io",
+    "travis-ci.",
+    "codecov.io",
+    "coveralls.io",
+    "app.netlify.com",
+];
+
+const TRACKING_PARAMS: [&str; 8] = ["fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "ref_src", "_hsenc"];
+
+pub struct LinkStats {
+    pub badges_removed: usize,
+    pub urls_cleaned: usize,
+    pub targets_dropped: usize,
+    pub aliases: Vec<(String, String)>,
+}
+
+/// Apply link compression to every file. `mode` is compact (badges, tracking
+/// params, aliases) or text (compact, plus external targets dropped).
+pub fn compress_links(files: &mut [SourceFile], mode: &str) -> LinkStats {
+    let re_linked_image = Regex::new(r"\[!\[[^\]]*\]\(([^)\s]+)[^)]*\)\]\(([^)\s]+)[^)]*\)").unwrap();
+    let re_image = Regex::new(r"!\[[^\]]*\]\(([^)\s]+)[^)]*\)").unwrap();
+    let re_url = Regex::new(r#"https?://[^\s)<>"'\]]+"#).unwrap();
+    // Images, including the inner image of a linked image, are left to html.rs
+    let re_link = Regex::new(r#"(!?)\[([^\[\]]*)\]\((https?://[^)\s]+)(?:\s+"[^"]*")?\)"#).unwrap();
+
+    let mut stats = LinkStats { badges_removed: 0, urls_cleaned: 0, targets_dropped: 0, aliases: Vec::new() };
+
+    // Badges and tracking parameters first, so aliasing sees clean URLs. Inline
+    // code is left as written, like fenced code
+    for file in files.iter_mut() {
+        file.content = map_prose(&file.content, |line| {
+            outside_code_spans(line, |text| {
+                let text = re_linked_image.replace_all(text, |caps: &Captures| {
+                    if is_badge(&caps[1]) {
+                        stats.badges_removed += 1;
+                        String::new()
+                    } else {
+                        caps[0].to_string()
+                    }
+                });
+                let text = re_image.replace_all(&text, |caps: &Captures| {
+                    if is_badge(&caps[1]) {
+                        stats.badges_removed += 1;
+                        String::new()
+                    } else {
+                        caps[0].to_string()
+                    }
+                });
+                re_url.replace_all(&text, |caps: &Captures| {
+                    let cleaned = strip_tracking(&caps[0]);
+                    if cleaned != caps[0] {
+                        stats.urls_cleaned += 1;
+                    }
+                    cleaned
+                }).to_string()
+            })
+        });
+    }
+
+    if mode == "text" {
+        for file in files.iter_mut() {
+            file.content = map_links(&file.content, &re_link, |caps| {
+                stats.targets_dropped += 1;
+                caps[2].to_string()
+            });
+        }
+        return stats;
+    }
+
+    // Alias URLs linked more than once, when the alias is actually shorter
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut first_seen = Vec::new();
+    for file in files.iter() {
+        map_links(&file.content, &re_link, |caps| {
+            let url = caps[3].to_string();
+            let count = counts.entry(url.clone()).or_insert(0);
+            if *count == 0 {
+                first_seen.push(url);
+            }
+            *count += 1;
+            String::new()
+        });
+    }
+
+    let aliases: HashMap<String, String> = first_seen
+        .into_iter()
+        .filter(|url| counts[url] > 1 && url.len() > 12)
+        .enumerate()
+        .map(|(i, url)| (url, format!("L{}", i + 1)))
+        .collect();
+    if aliases.is_empty() {
+        return stats;
+    }
+
+    for file in files.iter_mut() {
+        file.content = map_links(&file.content, &re_link, |caps| match aliases.get(&caps[3]) {
+            Some(alias) => format!("[{}][{}]", &caps[2], alias),
+            None => caps[0].to_string(),
+        });
+    }
+
+    let mut table: Vec<(String, String)> = aliases.into_iter().map(|(url, alias)| (alias, url)).collect();
+    table.sort_by_key(|(alias, _)| alias[1..].parse::<usize>().unwrap_or(0));
+    stats.aliases = table;
+    stats
+}
+
+pub fn format_link_table(aliases: &[(String, String)]) -> String {
+    if aliases.is_empty() {
+        return String::new();
+    }
+    let entries: Vec<String> = aliases.iter().map(|(alias, url)| format!("{}={}", alias, url)).collect();
+    format!("LINKS:{{{}}}\n", entries.join(","))
+}
+
+fn is_badge(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    BADGE_HOSTS.iter().any(|host| lower.contains(host))
+        || lower.contains("/badge.svg")
+        || lower.contains("/badge?")
+        || lower.contains("/badges/")
+}
+
+fn strip_tracking(url: &str) -> String {
+    let Some((base, rest)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or("");
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key)
+        })
+        .collect();
+
+    let mut cleaned = base.to_string();
+    if !kept.is_empty() {
+        cleaned.push('?');
+        cleaned.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        cleaned.push('#');
+        cleaned.push_str(fragment);
+    }
+    cleaned
+}
+
+/// Replace each external `[text](url)` link in prose with `f(captures)`
+/// (text in group 2, url in 3). Links inside code, and images, are passed over.
+fn map_links(content: &str, re_link: &Regex, mut f: impl FnMut(&Captures) -> String) -> String {
+    map_prose(content, |line| {
+        outside_code_spans(line, |text| {
+            re_link.replace_all(text, |caps: &Captures| {
+                if &caps[1] == "!" { caps[0].to_string() } else { f(caps) }
+            }).to_string()
+        })
+    })
+}
+
+/// Apply `f` to each line outside fenced code blocks.
+pub fn map_prose(content: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let output: Vec<String> = content
+        .lines()
+        .zip(fences::code_lines(content))
+        .map(|(line, in_code)| if in_code { line.to_string() } else { f(line) })
+        .collect();
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Apply `f` to the parts of a line outside inline `code` spans.
+pub fn outside_code_spans(line: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        let ticks = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        let fence = &rest[start..start + ticks];
+        let Some(end) = rest[start + ticks..].find(fence) else { break };
+        let end = start + ticks + end + ticks;
+        result.push_str(&f(&rest[..start]));
+        result.push_str(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    result.push_str(&f(rest));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(contents: &[&str]) -> Vec<SourceFile> {
+        contents
+            .iter()
+            .map(|content| SourceFile { content: content.to_string(), ..Default::default() })
+            .collect()
+    }
+
+    #[test]
+    fn images_are_not_aliased_as_links() {
+        let url = "https://example.com/diagram.png";
+        let mut docs = files(&[&format!("![Diagram]({url})\n"), &format!("![Diagram]({url}) and [png]({url})\n")]);
+        let stats = compress_links(&mut docs, "compact");
+        assert!(stats.aliases.is_empty());
+        assert_eq!(docs[1].content, format!("![Diagram]({url}) and [png]({url})\n"));
+
+        compress_links(&mut docs, "text");
+        assert_eq!(docs[1].content, format!("![Diagram]({url}) and png\n"));
+    }
+
+    #[test]
+    fn links_in_code_are_neither_counted_nor_rewritten() {
+        let url = "https://example.com/guide";
+        let mut docs = files(&[
+            &format!("See [the guide]({url}).\n\n```md\n[the guide]({url})\n```\n"),
+            &format!("Inline `[guide]({url})` only.\n"),
+        ]);
+        let stats = compress_links(&mut docs, "compact");
+        assert!(stats.aliases.is_empty());
+
+        docs[1].content.push_str(&format!("And [again]({url}).\n"));
+        let stats = compress_links(&mut docs, "compact");
+        assert_eq!(stats.aliases, [("L1".to_string(), url.to_string())]);
+        assert_eq!(docs[0].content, format!("See [the guide][L1].\n\n```md\n[the guide]({url})\n```\n"));
+        assert_eq!(docs[1].content, format!("Inline `[guide]({url})` only.\nAnd [again][L1].\n"));
+    }
+
+    #[test]
+    fn tracking_params_survive_in_inline_code() {
+        let mut docs = files(&["Open https://example.com/?utm_source=x&id=1 or run `curl 'https://example.com/?utm_source=x&id=1'`\n"]);
+        let stats = compress_links(&mut docs, "compact");
+        assert_eq!(stats.urls_cleaned, 1);
+        assert_eq!(docs[0].content, "Open https://example.com/?id=1 or run `curl 'https://example.com/?utm_source=x&id=1'`\n");
+    }
+
+    #[test]
+    fn map_prose_keeps_the_trailing_newline() {
+        assert_eq!(map_prose("a\nb\n", str::to_uppercase), "A\nB\n");
+        assert_eq!(map_prose("a\nb", str::to_uppercase), "A\nB");
+    }
+}
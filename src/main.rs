@@ -1,19 +1,42 @@
 use clap::Parser;
 use regex::Regex;
 use std::fs;
+use std::io::{Read, Write};
 use std::time::SystemTime;
 use walkdir::WalkDir;
 use chrono::{DateTime, Utc};
 
+mod diagrams;
+mod fences;
+mod directives;
+mod frontmatter;
+mod git;
+mod html;
+mod links;
+mod minify;
+mod models;
+mod nav;
+mod order;
+mod passes;
+mod refs;
+mod rules;
+mod sessions;
+mod snippets;
+mod structured;
+mod symbols;
+mod tables;
+#[cfg(test)]
+mod testutil;
+
 #[derive(Parser)]
 #[command(name = "verdant")]
 #[command(about = "Compress markdown files for AI consumption")]
 struct Args {
-    /// Input directory containing .md files
-    #[arg(short, long)]
-    input: String,
+    /// Input directories or .md files (repeatable, glob patterns allowed), or - for stdin
+    #[arg(short, long, required = true)]
+    input: Vec<String>,
     
-    /// Output file path (will be numbered for chunks: output_1.md, output_2.md, etc.)
+    /// Output file path (will be numbered for chunks: output_1.md, output_2.md, etc.), or - for stdout
     #[arg(short, long, default_value = "compressed")]
     output: String,
     
@@ -29,13 +52,17 @@ struct Args {
     #[arg(long)]
     chunk: bool,
     
-    /// Maximum lines per chunk (only used when chunking is enabled)
-    #[arg(long, default_value = "800")]
-    max_lines: usize,
+    /// Maximum lines per chunk (only used when chunking is enabled; defaults to the model profile's chunk_lines)
+    #[arg(long)]
+    max_lines: Option<usize>,
     
-    /// Target AI model (claude, gpt, copilot)
+    /// Target AI model (claude, gpt, copilot, or a profile from --models)
     #[arg(long, default_value = "claude")]
     model: String,
+
+    /// TOML file of extra or overridden model profiles (repeatable)
+    #[arg(long)]
+    models: Vec<String>,
     
     /// Enable AI-optimized extreme compression
     #[arg(long)]
@@ -45,10 +72,94 @@ struct Args {
     #[arg(long, default_value = "true")]
     chronological: bool,
 
+    /// Take file dates and authors from local git history instead of mtimes
+    #[arg(long)]
+    git: bool,
+
+    /// File order (modified, path, size, links, weight, first-commit, last-commit, commits); git modes imply --git
+    #[arg(long)]
+    order: Option<String>,
+
+    /// Order files as listed in a manifest (mdBook SUMMARY.md, mkdocs.yml nav, or a plain list)
+    #[arg(long)]
+    order_file: Option<String>,
+
+    /// Only include files whose frontmatter matches key=value or key!=value (repeatable), e.g. draft!=true
+    #[arg(long)]
+    filter: Vec<String>,
+
+    /// Follow docs-site navigation files (SUMMARY.md, mkdocs.yml, sidebars.js)
+    #[arg(long)]
+    nav: bool,
+
+    /// Only include markdown files added or modified since this git ref (merge base with HEAD)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// With --since, include compact diff hunks alongside the new content
+    #[arg(long, requires = "since")]
+    since_diff: bool,
+
     /// Remove emojis to save tokens  
     #[arg(long, default_value = "true")]  
     no_emojis: bool,
 
+    /// Replace directory prefixes shared by several files with short aliases (~1, ~2, ...)
+    #[arg(long)]
+    abbreviate_paths: bool,
+
+    /// Compact GFM tables (auto, rows, kv, keep); auto uses key=value records for two-column tables. Off unless given or selected with --passes +tables
+    #[arg(long)]
+    tables: Option<String>,
+
+    /// Link handling (compact, text, keep); compact strips badges and tracking params and aliases repeated URLs, text also drops external link targets. Off unless given or selected with --passes +links
+    #[arg(long)]
+    links: Option<String>,
+
+    /// Inline HTML handling (strip, keep); strip keeps tag text, turns <details><summary> into headings and drops comments. Off unless given or selected with --passes +html
+    #[arg(long)]
+    html: Option<String>,
+
+    /// Image handling (alt, drop, keep); alt replaces images with IMG:alt text. Off unless given or selected with --passes +html
+    #[arg(long)]
+    images: Option<String>,
+
+    /// Compression passes to run instead of the level's defaults (e.g. whitespace,headers,lists,dedup,math), or +name/-name to adjust them
+    #[arg(long)]
+    passes: Option<String>,
+
+    /// TOML file of [[rule]] regex rewrites (pattern, replacement, case_sensitive, scope, min_level); repeatable
+    #[arg(long)]
+    rules: Vec<String>,
+
+    /// JSON, YAML, TOML and XML code block handling (minify, keep); minify re-emits blocks that parse in their smallest valid form. Off unless given or selected with --passes +data-blocks
+    #[arg(long)]
+    data_blocks: Option<String>,
+
+    /// Truncate arrays in minified data blocks to N items plus a "…N more" marker
+    #[arg(long)]
+    max_array_items: Option<usize>,
+
+    /// Mermaid, PlantUML and Graphviz block handling (edges, keep); edges rewrites diagrams as A→B: label lists. Off unless given or selected with --passes +diagrams
+    #[arg(long)]
+    diagrams: Option<String>,
+
+    /// Shell block handling (normalize, keep); normalize strips prompts, joins \ continuations and marks command output OUT:. Off unless given or selected with --passes +shell-sessions
+    #[arg(long)]
+    shell_sessions: Option<String>,
+
+    /// Keep at most N output lines per command in shell sessions (0 drops output)
+    #[arg(long)]
+    max_output_lines: Option<usize>,
+
+    /// Add a SYMBOLS: header indexing the functions, types, commands and environment variables in code blocks
+    #[arg(long)]
+    symbols: bool,
+
+    /// VRD code block layout (appendix, inline); appendix moves code to trailing X: lines, inline keeps it in place within C: content
+    #[arg(long, default_value = "appendix")]
+    code_layout: String,
+
     /// Output format (md, vrd, json, yaml)
     #[arg(long, default_value = "md")]
     format: String,
@@ -62,15 +173,46 @@ struct CompressionStats {
     chunks_created: usize,
 }
 
+struct InputFile {
+    path: std::path::PathBuf,
+    relative: String, // Path relative to the input root it was found under
+    content: Option<String>, // Already read (stdin), otherwise loaded from path
+    modified: Option<SystemTime>,
+}
+
+#[cfg_attr(test, derive(Default))]
+struct SourceFile {
+    name: String,
+    content: String,
+    path: std::path::PathBuf,
+    modified: DateTime<Utc>,
+    created: Option<DateTime<Utc>>, // Only known from git history
+    authors: Vec<String>,
+    change: Option<String>, // "A" or "M" relative to --since
+    diff: Vec<String>,
+    section: Vec<String>, // Docs-site navigation sections containing the file
+    frontmatter: Option<frontmatter::Frontmatter>,
+    priority: Option<String>, // From a verdant:priority directive
+    verbatim: Vec<(usize, String)>, // verdant:verbatim regions, restored after compression
+}
+
 struct VrdFile {
     name: String,
     modified: DateTime<Utc>,
+    created: Option<DateTime<Utc>>,
+    authors: Vec<String>,
     size: usize,
     lines: usize,
     tags: Vec<String>,
-    headers: Vec<String>,
+    headers: Vec<(usize, String, String)>, // (level, title, anchor), in document order
     content: String,
     code_blocks: Vec<String>,
+    change: Option<String>,
+    diff: Vec<String>,
+    section: Vec<String>,
+    title: Option<String>,
+    priority: Option<String>,
+    languages: Vec<String>, // Code fence languages, for code-focused profiles
 }
 
 struct VrdMetadata {
@@ -85,14 +227,81 @@ fn main() {
     
     print_header(&args);
     
-    // Find all .md files
-    let md_files: Vec<_> = WalkDir::new(&args.input)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "md"))
-        .collect();
+    // Reject malformed --filter expressions before doing any work
+    for filter in &args.filter {
+        if let Err(e) = frontmatter::matches_filter(None, filter) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+    
+    // Resolve the target model profile
+    let profile = match models::load(&args.models).and_then(|profiles| models::find(&profiles, &args.model)) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    if !matches!(args.code_layout.as_str(), "inline" | "appendix") {
+        eprintln!("❌ Unsupported code layout: {} (expected inline or appendix)", args.code_layout);
+        std::process::exit(1);
+    }
     
-    println!("Found {} markdown files:", md_files.len());
+    // Load user rewrite rules, rejecting bad patterns before any work is done
+    let mut user_rules = Vec::new();
+    let mut selection = passes::Selection::default();
+    for path in &args.rules {
+        match rules::load(path) {
+            Ok(loaded) => {
+                eprintln!("📝 Loaded {} rules from {}", loaded.rules.len(), path);
+                user_rules.extend(loaded.rules);
+                if let Some(spec) = loaded.passes
+                    && let Err(e) = selection.apply(&spec) {
+                    eprintln!("❌ Invalid rules file: {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("❌ Invalid rules file: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    // --passes goes on top of any pass list from the rules files
+    if let Some(spec) = &args.passes
+        && let Err(e) = selection.apply(spec) {
+        eprintln!("❌ --passes: {}", e);
+        std::process::exit(1);
+    }
+    
+    // Find all .md files across every input root
+    let mut md_files = collect_input_files(&args.input);
+    
+    // Narrow to files changed on the branch
+    let mut changes = std::collections::HashMap::new();
+    if let Some(revision) = &args.since {
+        // Stdin has no git history to compare against
+        let piped = md_files.iter().filter(|file| file.content.is_some()).count();
+        if piped > 0 {
+            eprintln!("⚠️  --since skips {} file(s) read from stdin (no git history)", piped);
+        }
+        md_files.retain(|file| file.content.is_none());
+        let paths: Vec<std::path::PathBuf> = md_files.iter().map(|f| f.path.clone()).collect();
+        changes = match git::changes_since(&paths, revision) {
+            Ok(changes) => changes,
+            Err(e) => {
+                eprintln!("❌ --since {}: {}", revision, e);
+                std::process::exit(1);
+            }
+        };
+        md_files.retain(|file| changes.contains_key(&file.path));
+        eprintln!("🔀 {} markdown files changed since {}", md_files.len(), revision);
+    }
+    
+    eprintln!("Found {} markdown files:", md_files.len());
     
     let mut all_files_content = Vec::new();
     let mut stats = CompressionStats {
@@ -103,36 +312,375 @@ fn main() {
         chunks_created: 0,
     };
     
-    // Read all files with optional chronological sorting
-    read_all_files_with_sorting(&md_files, &mut all_files_content, &mut stats, args.stats, args.chronological);
+    // Read all files with optional chronological or history-based sorting
+    let order = match args.order.as_deref() {
+        Some(order @ ("modified" | "path" | "size" | "links" | "weight" | "first-commit" | "last-commit" | "commits")) => order,
+        Some(other) => {
+            eprintln!("❌ Unsupported order: {} (expected modified, path, size, links, weight, first-commit, last-commit or commits)", other);
+            std::process::exit(1);
+        }
+        None if args.chronological => "modified",
+        None => "none",
+    };
+    let use_git = args.git || matches!(order, "first-commit" | "last-commit" | "commits");
+    read_all_files_with_sorting(&md_files, &mut all_files_content, &mut stats, args.stats, order, use_git);
+    
+    // Split off frontmatter so later passes don't mangle it, then apply --filter
+    let mut filtered_out = 0;
+    all_files_content.retain_mut(|file| {
+        let (frontmatter, body) = frontmatter::split(&file.content, &file.name);
+        let keep = args.filter
+            .iter()
+            .all(|filter| frontmatter::matches_filter(frontmatter.as_ref(), filter).unwrap_or(false));
+        if !keep {
+            stats.original_size -= file.content.len();
+            stats.original_lines -= file.content.lines().count();
+            filtered_out += 1;
+            return false;
+        }
+        
+        // Declared dates beat mtimes, but not git history
+        if let Some(date) = frontmatter.as_ref().and_then(|fm| fm.date)
+            && file.created.is_none() {
+            file.modified = date;
+        }
+        file.content = body;
+        file.frontmatter = frontmatter;
+        true
+    });
+    if filtered_out > 0 {
+        eprintln!("🔎 Skipped {} files not matching --filter", filtered_out);
+    }
+    
+    // Honour verdant: directives written in the documents themselves
+    let mut verbatim_ids = 0;
+    let mut skipped_lines = 0;
+    for file in all_files_content.iter_mut() {
+        let (content, file_directives) = directives::apply(&file.content, &file.name, &mut verbatim_ids);
+        file.content = content;
+        file.priority = file_directives.priority;
+        file.verbatim = file_directives.verbatim;
+        skipped_lines += file_directives.skipped_lines;
+    }
+    if skipped_lines > 0 {
+        eprintln!("⏭️  Skipped {} lines marked verdant:skip", skipped_lines);
+    }
+    if verbatim_ids > 0 {
+        eprintln!("📌 Keeping {} verbatim regions untouched", verbatim_ids);
+    }
+    
+    // User rewrite rules, after directives so verbatim regions stay untouched
+    if !user_rules.is_empty() {
+        let mut rule_replacements = 0;
+        for file in all_files_content.iter_mut() {
+            let (content, count) = rules::apply(&user_rules, &file.content, &args.level);
+            file.content = content;
+            rule_replacements += count;
+        }
+        eprintln!("📝 Applied {} rule replacements", rule_replacements);
+    }
+    
+    // Orderings that need file content or a manifest. Chronological orders
+    // run again now that frontmatter dates have replaced mtimes
+    match order {
+        "modified" | "last-commit" => all_files_content.sort_by_key(|file| file.modified),
+        "first-commit" => all_files_content.sort_by_key(|file| file.created.unwrap_or(file.modified)),
+        "path" => {
+            order::sort_by_path(&mut all_files_content);
+            eprintln!("🔤 Files sorted by path");
+        }
+        "size" => {
+            order::sort_by_size(&mut all_files_content);
+            eprintln!("📏 Files sorted by size (smallest first)");
+        }
+        "links" => {
+            order::sort_by_links(&mut all_files_content);
+            eprintln!("🔗 Files sorted by links (prerequisites first)");
+        }
+        "weight" => {
+            order::sort_by_weight(&mut all_files_content);
+            eprintln!("⚖️  Files sorted by frontmatter weight");
+        }
+        _ => {}
+    }
+    if let Some(manifest) = &args.order_file {
+        match order::sort_by_manifest(&mut all_files_content, std::path::Path::new(manifest)) {
+            Ok(matched) => eprintln!("📑 Ordered {} files from {}", matched, manifest),
+            Err(e) => {
+                eprintln!("❌ Error reading order file: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    // Follow the docs site's own navigation when there is one
+    let site_nav = if args.nav { nav::detect(&args.input) } else { None };
+    if let Some(site) = &site_nav {
+        // The outline replaces the navigation file itself (mdBook SUMMARY.md)
+        all_files_content.retain(|file| fs::canonicalize(&file.path).ok() != site.source);
+        
+        let pages = site.pages();
+        if args.order.is_none() && args.order_file.is_none() {
+            let listed: Vec<std::path::PathBuf> = pages.iter().map(|(path, _)| path.clone()).collect();
+            let matched = order::sort_by_listing(&mut all_files_content, &listed);
+            eprintln!("📑 Ordered {} files by site navigation", matched);
+        }
+        for file in all_files_content.iter_mut() {
+            let canonical = fs::canonicalize(&file.path).ok();
+            if let Some((_, trail)) = pages.iter().find(|(path, _)| Some(path) == canonical.as_ref()) {
+                file.section = trail[..trail.len() - 1].to_vec();
+            }
+        }
+    }
+    
+    // High-priority files lead, low-priority files trail, unless the order was chosen explicitly
+    if args.order.is_none() && args.order_file.is_none() && all_files_content.iter().any(|f| f.priority.is_some()) {
+        all_files_content.sort_by_key(|file| match file.priority.as_deref() {
+            Some("high") => 0,
+            Some("low") => 2,
+            _ => 1,
+        });
+        eprintln!("⭐ Files with verdant:priority moved accordingly");
+    }
+    
+    // Record what changed relative to --since
+    for file in all_files_content.iter_mut() {
+        match changes.remove(&file.path) {
+            Some(git::FileChange::Added) => file.change = Some("A".to_string()),
+            Some(git::FileChange::Modified { previous }) => {
+                file.change = Some("M".to_string());
+                if args.since_diff {
+                    file.diff = git::compact_diff(&previous, &file.content);
+                }
+            }
+            None => {}
+        }
+    }
+    
+    // Rewrite GFM tables into the compact encoding
+    let selected = |name: &str| selection.includes(name, &args.format, &args.level, args.ai_mode);
+    match content_pass_mode(args.tables.as_deref(), "auto", selected("tables")) {
+        None => {}
+        Some(mode @ ("auto" | "rows" | "kv")) => {
+            let mut tables_compacted = 0;
+            let mut table_bytes_saved = 0;
+            for file in all_files_content.iter_mut() {
+                let (compacted, count) = tables::compact_tables(&file.content, mode);
+                if count > 0 {
+                    table_bytes_saved += file.content.len().saturating_sub(compacted.len());
+                    tables_compacted += count;
+                    file.content = compacted;
+                }
+            }
+            if tables_compacted > 0 {
+                eprintln!("📋 Compacted {} tables (~{} chars saved)", tables_compacted, table_bytes_saved);
+            }
+        }
+        Some(other) => {
+            eprintln!("❌ Unsupported table mode: {} (expected auto, rows, kv or keep)", other);
+            std::process::exit(1);
+        }
+    }
+    
+    // Re-emit structured data blocks in their minimal valid form
+    match content_pass_mode(args.data_blocks.as_deref(), "minify", selected("data-blocks")) {
+        None => {}
+        Some("minify") => {
+            let mut blocks_minified = 0;
+            let mut data_bytes_saved = 0;
+            for file in all_files_content.iter_mut() {
+                let (minified, count) = structured::minify_blocks(&file.content, args.max_array_items);
+                if count > 0 {
+                    data_bytes_saved += file.content.len().saturating_sub(minified.len());
+                    blocks_minified += count;
+                    file.content = minified;
+                }
+            }
+            if blocks_minified > 0 {
+                eprintln!("🗜️  Minified {} data blocks (~{} chars saved)", blocks_minified, data_bytes_saved);
+            }
+        }
+        Some(other) => {
+            eprintln!("❌ Unsupported data block mode: {} (expected minify or keep)", other);
+            std::process::exit(1);
+        }
+    }
+    
+    // Turn diagram sources into edge lists
+    match content_pass_mode(args.diagrams.as_deref(), "edges", selected("diagrams")) {
+        None => {}
+        Some("edges") => {
+            let mut diagrams_converted = 0;
+            for file in all_files_content.iter_mut() {
+                let (converted, count) = diagrams::compress_diagrams(&file.content);
+                diagrams_converted += count;
+                file.content = converted;
+            }
+            if diagrams_converted > 0 {
+                eprintln!("🔀 Converted {} diagrams to edge lists", diagrams_converted);
+            }
+        }
+        Some(other) => {
+            eprintln!("❌ Unsupported diagram mode: {} (expected edges or keep)", other);
+            std::process::exit(1);
+        }
+    }
+    
+    // Separate shell commands from their output
+    match content_pass_mode(args.shell_sessions.as_deref(), "normalize", selected("shell-sessions")) {
+        None => {}
+        Some("normalize") => {
+            let mut session_stats = sessions::SessionStats::default();
+            for file in all_files_content.iter_mut() {
+                file.content = sessions::normalize_blocks(&file.content, args.max_output_lines, &mut session_stats);
+            }
+            if session_stats.prompts_stripped + session_stats.continuations_joined > 0 {
+                eprintln!("💲 Normalized {} shell blocks ({} prompts stripped, {} continued commands joined)",
+                    session_stats.blocks, session_stats.prompts_stripped, session_stats.continuations_joined);
+            }
+            if session_stats.output_lines_dropped > 0 {
+                eprintln!("✂️  Dropped {} lines of command output", session_stats.output_lines_dropped);
+            }
+        }
+        Some(other) => {
+            eprintln!("❌ Unsupported shell session mode: {} (expected normalize or keep)", other);
+            std::process::exit(1);
+        }
+    }
+    
+    // Strip badges and tracking params, alias repeated URLs
+    let mut link_aliases = Vec::new();
+    match content_pass_mode(args.links.as_deref(), "compact", selected("links")) {
+        None => {}
+        Some(mode @ ("compact" | "text")) => {
+            let link_stats = links::compress_links(&mut all_files_content, mode);
+            if link_stats.badges_removed > 0 {
+                eprintln!("🏷️  Removed {} badges", link_stats.badges_removed);
+            }
+            if link_stats.urls_cleaned > 0 {
+                eprintln!("🧹 Stripped tracking parameters from {} URLs", link_stats.urls_cleaned);
+            }
+            if link_stats.targets_dropped > 0 {
+                eprintln!("🔗 Dropped {} external link targets", link_stats.targets_dropped);
+            }
+            if !link_stats.aliases.is_empty() {
+                eprintln!("🔗 Aliased {} repeated URLs", link_stats.aliases.len());
+            }
+            link_aliases = link_stats.aliases;
+        }
+        Some(other) => {
+            eprintln!("❌ Unsupported link mode: {} (expected compact, text or keep)", other);
+            std::process::exit(1);
+        }
+    }
+    
+    // Strip presentational HTML and shrink images to their alt text
+    let html_selected = selected("html");
+    let html_mode = args.html.as_deref().unwrap_or(if html_selected { "strip" } else { "keep" });
+    let image_mode = args.images.as_deref().unwrap_or(if html_selected { "alt" } else { "keep" });
+    if !matches!(html_mode, "strip" | "keep") {
+        eprintln!("❌ Unsupported HTML mode: {} (expected strip or keep)", html_mode);
+        std::process::exit(1);
+    }
+    if !matches!(image_mode, "alt" | "drop" | "keep") {
+        eprintln!("❌ Unsupported image mode: {} (expected alt, drop or keep)", image_mode);
+        std::process::exit(1);
+    }
+    if html_mode != "keep" || image_mode != "keep" {
+        let mut html_stats = html::HtmlStats::default();
+        for file in all_files_content.iter_mut() {
+            file.content = html::compress_html(&file.content, html_mode, image_mode, &mut html_stats);
+        }
+        if html_stats.tags_stripped + html_stats.comments_removed > 0 {
+            eprintln!("🧩 Stripped {} HTML tags and {} comments", html_stats.tags_stripped, html_stats.comments_removed);
+        }
+        if html_stats.details_converted > 0 {
+            eprintln!("📂 Converted {} <details> blocks to sections", html_stats.details_converted);
+        }
+        if html_stats.images_converted + html_stats.images_dropped > 0 {
+            eprintln!("🖼️  Images: {} reduced to alt text, {} dropped", html_stats.images_converted, html_stats.images_dropped);
+        }
+    }
+    
+    // Shorten repeated directory prefixes in file identities
+    let path_aliases = if args.abbreviate_paths {
+        abbreviate_path_prefixes(&mut all_files_content)
+    } else {
+        Vec::new()
+    };
+    
+    // Point relative links at the bundled records they refer to
+    let ref_stats = refs::resolve_internal_links(&mut all_files_content);
+    if ref_stats.rewritten > 0 {
+        eprintln!("🔗 Resolved {} links to bundled files", ref_stats.rewritten);
+    }
+    for (name, target) in &ref_stats.broken {
+        eprintln!("⚠️  Broken link in {}: {}", name, target);
+    }
+    
+    // Index code symbols before snippets and dedup take blocks out of files
+    let symbol_index = if args.symbols {
+        symbols::build_index(&all_files_content)
+    } else {
+        Vec::new()
+    };
+    if !symbol_index.is_empty() {
+        eprintln!("🔣 Indexed {} code symbols", symbol_index.len());
+    }
+    
+    // Store code blocks repeated across files once, referenced by id
+    let mut snippet_table = Vec::new();
+    if selection.includes("snippets", &args.format, &args.level, args.ai_mode) {
+        snippet_table = snippets::dedup_code_blocks(&mut all_files_content);
+        if !snippet_table.is_empty() {
+            let uses: usize = snippet_table.iter().map(|snippet| snippet.uses).sum();
+            eprintln!("🧬 Shared {} repeated code blocks as snippets ({} references)", snippet_table.len(), uses);
+        }
+    }
     
     // Remove duplicates if needed
-    if args.level != "low" {
-        println!("\n🔄 Removing duplicate content across files...");
+    if selection.includes("dedup", &args.format, &args.level, args.ai_mode) {
+        eprintln!("\n🔄 Removing duplicate content across files...");
         all_files_content = remove_duplicate_content(all_files_content, args.stats);
     }
     
     // Show emoji removal stats if enabled
     if args.no_emojis {
         let emoji_count: usize = all_files_content.iter()
-            .map(|(_, content, _)| count_emojis(content))
+            .map(|file| count_emojis(&file.content))
             .sum();
         if emoji_count > 0 {
-            println!("🚫 Removed {} emojis (~{} tokens saved)", emoji_count, emoji_count * 2);
+            eprintln!("🚫 Removed {} emojis (~{} tokens saved)", emoji_count, emoji_count * 2);
         }
     }
     
+    // Extra bundle header lines: path aliases, links, snippets, symbols and the site outline
+    let mut header_extras = format_path_aliases(&path_aliases);
+    header_extras.push_str(&links::format_link_table(&link_aliases));
+    header_extras.push_str(&snippets::format_snippet_table(&snippet_table));
+    header_extras.push_str(&symbols::format_symbol_index(&symbol_index));
+    if let Some(site) = &site_nav {
+        header_extras.push_str(&format_nav_outline(site, &all_files_content));
+    }
+    
     // Compress content
-    let compressed_content = compress_all_content(&all_files_content, &args);
+    let compressed_content = compress_all_content(&all_files_content, &header_extras, &args, &selection, &profile);
     
     // Handle chunking or single file output
     if args.chunk {
-        create_chunks(&compressed_content, &args, &mut stats);
+        create_chunks(&compressed_content, &args, &profile, &mut stats);
     } else {
         write_single_file(&compressed_content, &args, &mut stats);
     }
     
-    print_final_stats(&stats, args.stats);
+    // A single output the model can't take in whole is worth flagging
+    let estimated_tokens = profile.estimate_tokens(stats.compressed_size);
+    if !args.chunk && estimated_tokens > profile.context_window {
+        eprintln!("⚠️  Output is ~{} tokens, over the {}-token context window of {}. Consider --chunk.",
+                 estimated_tokens, profile.context_window, profile.name);
+    }
+    
+    print_final_stats(&stats, args.stats, &profile);
 }
 
 fn count_emojis(content: &str) -> usize {
@@ -143,8 +691,8 @@ fn count_emojis(content: &str) -> usize {
 
 
 fn print_header(args: &Args) {
-    println!("🌱 verdant v2.3");
-    println!("  Compressing markdown for AI consumption");
+    eprintln!("🌱 verdant v2.3");
+    eprintln!("  Compressing markdown for AI consumption");
     
     let mut features = vec![
         format!("Target: {}", args.model),
@@ -165,58 +713,384 @@ fn print_header(args: &Args) {
         features.push("AI mode: enabled".to_string());
     }
     
-    println!("  {}", features.join(" | "));
-    println!();
-    println!("Input: {}", args.input);
+    eprintln!("  {}", features.join(" | "));
+    eprintln!();
+    eprintln!("Input: {}", args.input.join(", "));
     
     let extension = if args.format == "vrd" { "vrd" } else { "md" };
-    println!("Output: {}", if args.chunk { 
+    eprintln!("Output: {}", if args.output == "-" {
+        "stdout".to_string()
+    } else if args.chunk { 
         format!("{}_*.{}", args.output, extension) 
     } else { 
         format!("{}.{}", args.output, extension) 
     });
-    println!();
+    eprintln!();
+}
+
+fn collect_input_files(inputs: &[String]) -> Vec<InputFile> {
+    let mut files = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicates = 0;
+    let mut stdin_read = false;
+    
+    // Expand every input first: identities depend on how many roots there are
+    let mut roots: Vec<std::path::PathBuf> = Vec::new();
+    for input in inputs {
+        if input == "-" {
+            // Stdin can only be read once; a second - would add an empty stdin.md
+            if stdin_read {
+                eprintln!("⚠️  Ignoring repeated - input (stdin is read once)");
+            } else {
+                files.extend(read_stdin_inputs());
+                stdin_read = true;
+            }
+            continue;
+        }
+        
+        // Expand patterns the shell left alone (e.g. quoted 'crates/*/README.md')
+        if input.contains(['*', '?', '[']) {
+            match glob::glob(input) {
+                Ok(paths) => {
+                    let before = roots.len();
+                    roots.extend(paths.filter_map(|p| p.ok()));
+                    if roots.len() == before {
+                        eprintln!("⚠️  No files match input: {}", input);
+                    }
+                }
+                Err(e) => eprintln!("❌ Invalid input pattern {}: {}", input, e),
+            }
+        } else {
+            roots.push(std::path::PathBuf::from(input));
+        }
+    }
+    
+    // (path, number of trailing path components that make up its identity)
+    let mut found: Vec<(std::path::PathBuf, usize)> = Vec::new();
+    for root in &roots {
+        let mut root_files = Vec::new();
+        if root.is_dir() {
+            for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+                if entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "md") {
+                    // Relative to the root, plus the root's own name when there are
+                    // several roots, so docs/README.md and crates/README.md don't collide
+                    let depth = entry.path().strip_prefix(root).map_or(1, |p| p.components().count());
+                    root_files.push((entry.path().to_path_buf(), depth + usize::from(roots.len() > 1)));
+                }
+            }
+        } else if root.is_file() {
+            root_files.push((root.clone(), 1));
+        } else {
+            eprintln!("⚠️  Input not found: {}", root.display());
+        }
+        
+        for (path, depth) in root_files {
+            let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if seen.insert(key) {
+                found.push((path, depth));
+            } else {
+                duplicates += 1;
+                eprintln!("  🔁 Skipping {} (already included via another input)", path.display());
+            }
+        }
+    }
+    if duplicates > 0 {
+        eprintln!("   Skipped {} files listed under more than one input", duplicates);
+    }
+    
+    let identities = unique_identities(&found);
+    files.extend(found.into_iter().zip(identities).map(|((path, _), relative)| {
+        InputFile { path, relative, content: None, modified: None }
+    }));
+    files
+}
+
+/// File identities from the trailing path components of each file, extended
+/// one directory at a time until no two files share one (shortest unique suffix).
+fn unique_identities(found: &[(std::path::PathBuf, usize)]) -> Vec<String> {
+    let components: Vec<Vec<String>> = found
+        .iter()
+        .map(|(path, _)| {
+            // Absolute paths for the extra components, never ../ or a bare root
+            let full = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            full.components()
+                .filter_map(|c| match c {
+                    std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let mut lengths: Vec<usize> = found.iter().zip(&components).map(|((_, depth), parts)| (*depth).min(parts.len())).collect();
+    let identity = |i: usize, lengths: &[usize]| components[i][components[i].len() - lengths[i]..].join("/");
+    
+    loop {
+        let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+        for i in 0..found.len() {
+            *counts.entry(identity(i, &lengths)).or_default() += 1;
+        }
+        let mut extended = false;
+        for i in 0..found.len() {
+            if counts[&identity(i, &lengths)] > 1 && lengths[i] < components[i].len() {
+                lengths[i] += 1;
+                extended = true;
+            }
+        }
+        if !extended {
+            break;
+        }
+    }
+    
+    (0..found.len()).map(|i| identity(i, &lengths)).collect()
+}
+
+fn read_stdin_inputs() -> Vec<InputFile> {
+    let mut buffer = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut buffer) {
+        eprintln!("❌ Error reading stdin: {}", e);
+        return Vec::new();
+    }
+    
+    // POSIX tar archives carry "ustar" at offset 257 of the first header
+    let is_tar = buffer.len() > 262 && &buffer[257..262] == b"ustar";
+    if !is_tar {
+        return vec![InputFile {
+            path: std::path::PathBuf::from("stdin.md"),
+            relative: "stdin.md".to_string(),
+            content: Some(String::from_utf8_lossy(&buffer).to_string()),
+            modified: Some(SystemTime::now()),
+        }];
+    }
+    
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(buffer.as_slice());
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("❌ Error reading tar stream from stdin: {}", e);
+            return files;
+        }
+    };
+    
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("❌ Error reading tar entry: {}", e);
+                break;
+            }
+        };
+        
+        let path = match entry.path() {
+            Ok(path) => path.to_path_buf(),
+            Err(_) => continue,
+        };
+        if !entry.header().entry_type().is_file() || path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        
+        let modified = entry.header().mtime().ok()
+            .map(|secs| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        let mut content = String::new();
+        match entry.read_to_string(&mut content) {
+            Ok(_) => files.push(InputFile {
+                relative: display_path(&path),
+                path,
+                content: Some(content),
+                modified,
+            }),
+            Err(e) => eprintln!("Error reading {} from stdin: {}", path.display(), e),
+        }
+    }
+    
+    files
+}
+
+/// The mode a content pass runs in, or None to skip it. A mode given on the
+/// command line wins (`keep` turns the pass off); otherwise the pass runs in
+/// its default mode when the pass selection includes it.
+fn content_pass_mode<'a>(flag: Option<&'a str>, default: &'a str, selected: bool) -> Option<&'a str> {
+    match flag {
+        Some("keep") => None,
+        Some(mode) => Some(mode),
+        None => selected.then_some(default),
+    }
+}
+
+fn display_path(path: &std::path::Path) -> String {
+    let display = path.to_string_lossy().replace('\\', "/");
+    display.trim_start_matches("./").to_string()
 }
 
 fn read_all_files_with_sorting(
-    md_files: &[walkdir::DirEntry], 
-    all_files_content: &mut Vec<(String, String, std::path::PathBuf)>, // Add PathBuf
+    md_files: &[InputFile], 
+    all_files_content: &mut Vec<SourceFile>,
     stats: &mut CompressionStats,
     show_stats: bool,
-    chronological: bool
+    order: &str,
+    use_git: bool
 ) {
-    let mut files_with_time: Vec<(walkdir::DirEntry, SystemTime)> = Vec::new();
+    let mut histories = if use_git {
+        let paths: Vec<std::path::PathBuf> = md_files
+            .iter()
+            .filter(|file| file.content.is_none())
+            .map(|file| file.path.clone())
+            .collect();
+        let histories = git::file_histories(&paths);
+        eprintln!("🌿 Found git history for {} of {} files", histories.len(), md_files.len());
+        histories
+    } else {
+        std::collections::HashMap::new()
+    };
+    
+    let mut files_with_time: Vec<(&InputFile, SystemTime, Option<git::FileHistory>)> = Vec::new();
     
     for file in md_files {
-        if let Ok(metadata) = file.metadata() {
+        let history = histories.remove(&file.path);
+        if let Some(modified_time) = file.modified {
+            files_with_time.push((file, modified_time, history));
+        } else if let Ok(metadata) = fs::metadata(&file.path) {
             let modified_time = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-            files_with_time.push((file.clone(), modified_time));
+            files_with_time.push((file, modified_time, history));
         }
     }
     
-    if chronological {
-        files_with_time.sort_by(|a, b| a.1.cmp(&b.1));
-        println!("📅 Files sorted chronologically (oldest → newest)");
+    // Files without history keep their mtime so they still sort sensibly
+    let last_change = |(_, mtime, history): &(&InputFile, SystemTime, Option<git::FileHistory>)| {
+        history.as_ref().map_or(DateTime::<Utc>::from(*mtime), |h| h.modified)
+    };
+    
+    match order {
+        "modified" | "last-commit" => {
+            files_with_time.sort_by_key(last_change);
+            eprintln!("📅 Files sorted chronologically (oldest → newest)");
+        }
+        "first-commit" => {
+            files_with_time.sort_by_key(|entry| entry.2.as_ref().map_or(last_change(entry), |h| h.created));
+            eprintln!("📅 Files sorted by first commit (oldest → newest)");
+        }
+        "commits" => {
+            files_with_time.sort_by_key(|entry| std::cmp::Reverse(entry.2.as_ref().map_or(0, |h| h.commits)));
+            eprintln!("📅 Files sorted by commit frequency (most edited first)");
+        }
+        _ => {}
     }
     
-    for (file, _) in files_with_time {
-        println!("  📄 {}", file.path().display());
+    for (file, mtime, history) in files_with_time {
+        eprintln!("  📄 {}", file.relative);
         
-        match fs::read_to_string(file.path()) {
+        let read_result = match &file.content {
+            Some(content) => Ok(content.clone()),
+            None => fs::read_to_string(&file.path),
+        };
+        
+        match read_result {
             Ok(content) => {
                 stats.original_size += content.len();
                 stats.original_lines += content.lines().count();
                 
                 if show_stats {
-                    println!("    Lines: {}, Chars: {}", content.lines().count(), content.len());
+                    eprintln!("    Lines: {}, Chars: {}", content.lines().count(), content.len());
+                    if let Some(history) = &history {
+                        eprintln!("    Commits: {}, Authors: {}", history.commits, history.authors.join(", "));
+                    }
                 }
                 
-                let filename = file.path().file_name().unwrap().to_str().unwrap().to_string();
-                all_files_content.push((filename, content, file.path().to_path_buf()));
+                let (modified, created, authors) = match history {
+                    Some(h) => (h.modified, Some(h.created), h.authors),
+                    None => (DateTime::<Utc>::from(mtime), None, Vec::new()),
+                };
+                
+                all_files_content.push(SourceFile {
+                    name: file.relative.clone(),
+                    content,
+                    path: file.path.clone(),
+                    modified,
+                    created,
+                    authors,
+                    change: None,
+                    diff: Vec::new(),
+                    section: Vec::new(),
+                    frontmatter: None,
+                    priority: None,
+                    verbatim: Vec::new(),
+                });
+            }
+            Err(e) => eprintln!("Error reading {}: {}", file.path.display(), e),
+        }
+    }
+}
+
+fn abbreviate_path_prefixes(all_files_content: &mut [SourceFile]) -> Vec<(String, String)> {
+    // Count how many files live directly in each directory
+    let mut dir_counts: Vec<(String, usize)> = Vec::new();
+    for file in all_files_content.iter() {
+        if let Some((dir, _)) = file.name.rsplit_once('/') {
+            match dir_counts.iter_mut().find(|(d, _)| d == dir) {
+                Some((_, count)) => *count += 1,
+                None => dir_counts.push((dir.to_string(), 1)),
+            }
+        }
+    }
+    
+    // Only alias directories where the alias actually saves characters
+    let aliases: Vec<(String, String)> = dir_counts
+        .into_iter()
+        .filter(|(dir, count)| *count > 1 && dir.len() > 4)
+        .enumerate()
+        .map(|(i, (dir, _))| (format!("~{}", i + 1), dir))
+        .collect();
+    
+    for file in all_files_content.iter_mut() {
+        if let Some((dir, base)) = file.name.rsplit_once('/')
+            && let Some((alias, _)) = aliases.iter().find(|(_, d)| d == dir) {
+            file.name = format!("{}/{}", alias, base);
+        }
+    }
+    
+    if !aliases.is_empty() {
+        eprintln!("📁 Abbreviated {} shared path prefixes", aliases.len());
+    }
+    
+    aliases
+}
+
+fn format_path_aliases(path_aliases: &[(String, String)]) -> String {
+    if path_aliases.is_empty() {
+        return String::new();
+    }
+    
+    let entries: Vec<String> = path_aliases
+        .iter()
+        .map(|(alias, dir)| format!("{}={}", alias, dir))
+        .collect();
+    format!("PATHS:{{{}}}\n", entries.join(","))
+}
+
+fn format_nav_outline(site: &nav::SiteNav, all_files_content: &[SourceFile]) -> String {
+    // Map canonical paths back to the file identities used in F: records
+    let names: std::collections::HashMap<std::path::PathBuf, &str> = all_files_content
+        .iter()
+        .filter_map(|file| fs::canonicalize(&file.path).ok().map(|p| (p, file.name.as_str())))
+        .collect();
+    
+    fn render(nodes: &[nav::NavNode], depth: usize, names: &std::collections::HashMap<std::path::PathBuf, &str>, out: &mut String) {
+        for node in nodes {
+            out.push_str(&" ".repeat(depth));
+            out.push('•');
+            out.push_str(&node.title);
+            if let Some(name) = node.path.as_ref().and_then(|p| names.get(p)) {
+                out.push('=');
+                out.push_str(name);
             }
-            Err(e) => println!("Error reading {}: {}", file.path().display(), e),
+            out.push('\n');
+            render(&node.children, depth + 1, names, out);
         }
     }
+    
+    let mut outline = format!("NAV:{}\n", site.kind);
+    render(&site.nodes, 0, &names, &mut outline);
+    outline
 }
 
 fn remove_emojis(content: &str) -> String {
@@ -242,62 +1116,89 @@ fn remove_emojis(content: &str) -> String {
     result
 }
 
-fn compress_all_content(all_files_content: &[(String, String, std::path::PathBuf)], args: &Args) -> String {
+fn compress_all_content(all_files_content: &[SourceFile], header_extras: &str, args: &Args, selection: &passes::Selection, profile: &models::Profile) -> String {
     match args.format.as_str() {
         "vrd" => {
             // Warn if using VRD format with single file (inefficient due to overhead)
             if all_files_content.len() == 1 {
-                println!("⚠️  WARNING: VRD format with single file may be less efficient due to format overhead.");
-                println!("   Consider using regular markdown compression (remove --format vrd) for single files.");
-                println!("   VRD format is optimized for multi-file documentation sets.\n");
+                eprintln!("⚠️  WARNING: VRD format with single file may be less efficient due to format overhead.");
+                eprintln!("   Consider using regular markdown compression (remove --format vrd) for single files.");
+                eprintln!("   VRD format is optimized for multi-file documentation sets.\n");
             }
             
             // Calculate original stats for VRD
             let original_stats = CompressionStats {
-                original_size: all_files_content.iter().map(|(_, c, _)| c.len()).sum(),
+                original_size: all_files_content.iter().map(|f| f.content.len()).sum(),
                 compressed_size: 0,
-                original_lines: all_files_content.iter().map(|(_, c, _)| c.lines().count()).sum(),
+                original_lines: all_files_content.iter().map(|f| f.content.lines().count()).sum(),
                 compressed_lines: 0,
                 chunks_created: 0,
             };
-            generate_vrd_content(all_files_content, args, &original_stats)
+            generate_vrd_content(all_files_content, header_extras, args, selection, profile, &original_stats)
         }
         "md" => {
             // Existing markdown generation...
             let mut combined_content = String::new();
-            combined_content.push_str(&create_model_header(&args.model, args.ai_mode));
+            combined_content.push_str(&create_model_header(profile, args.ai_mode, header_extras));
             
-            for (filename, content, _) in all_files_content {
-                combined_content.push_str(&format!("F:{}\n", filename));
-                let compressed = compress_content(content, &args.level, &args.model, args.ai_mode, args.no_emojis);
+            for file in all_files_content {
+                combined_content.push_str(&format!("F:{}", file.name));
+                if let Some(title) = file.frontmatter.as_ref().and_then(|fm| fm.title.as_ref()) {
+                    combined_content.push_str(&format!("|TI:{}", title));
+                }
+                if let Some(tags) = file.frontmatter.as_ref().map(|fm| &fm.tags).filter(|t| !t.is_empty()) {
+                    combined_content.push_str(&format!("|T:{}", tags.join(",")));
+                }
+                if !file.section.is_empty() {
+                    combined_content.push_str(&format!("|SEC:{}", file.section.join("›")));
+                }
+                if let Some(priority) = &file.priority {
+                    combined_content.push_str(&format!("|P:{}", priority));
+                }
+                // File-type hints for code-focused profiles
+                if profile.optimize == "code" {
+                    let languages = fence_languages(&file.content);
+                    if !languages.is_empty() {
+                        combined_content.push_str(&format!("|LANG:{}", languages.join(",")));
+                    }
+                }
+                if let Some(change) = &file.change {
+                    combined_content.push_str(&format!("|Δ:{}", change));
+                }
+                combined_content.push('\n');
+                let compressed = directives::compress_regions(&file.content, &args.level, |text, level| {
+                    compress_content(text, &selection.passes("md", level, args.ai_mode), profile, args.no_emojis)
+                });
+                let compressed = directives::restore_verbatim(&compressed, &file.verbatim);
                 combined_content.push_str(&compressed);
+                for line in &file.diff {
+                    combined_content.push_str(&format!("\nΔ{}", line));
+                }
                 combined_content.push_str("\n|\n");
             }
             
             combined_content
         }
         _ => {
-            println!("❌ Unsupported format: {}", args.format);
+            eprintln!("❌ Unsupported format: {}", args.format);
             std::process::exit(1);
         }
     }
 }
 
-fn create_model_header(model: &str, ai_mode: bool) -> String {
-    let mut header = format!("TARGET:{}\n", model.to_uppercase());
+fn create_model_header(profile: &models::Profile, ai_mode: bool, header_extras: &str) -> String {
+    let mut header = format!("TARGET:{}\n", profile.name.to_uppercase());
     
     if ai_mode {
         header.push_str("MODE:AI_OPTIMIZED\n");
         header.push_str(&create_abbreviation_dictionary());
     }
     
-    match model {
-        "claude" => header.push_str("NOTE:Structured data with technical notation\n"),
-        "gpt" => header.push_str("NOTE:Consistent formatting with explicit context\n"),
-        "copilot" => header.push_str("NOTE:Code-focused with file-type hints\n"),
-        _ => {}
+    if !profile.note.is_empty() {
+        header.push_str(&format!("NOTE:{}\n", profile.note));
     }
     
+    header.push_str(header_extras);
     header.push_str("---\n");
     header
 }
@@ -318,7 +1219,7 @@ fn create_abbreviation_dictionary() -> String {
     ];
     
     for (i, (full, abbrev)) in abbreviations.iter().enumerate() {
-        if i > 0 { dict.push_str(","); }
+        if i > 0 { dict.push(','); }
         dict.push_str(&format!("{}={}", abbrev, full));
     }
     dict.push_str("}\n");
@@ -326,18 +1227,28 @@ fn create_abbreviation_dictionary() -> String {
 }
 
 
-fn create_chunks(content: &str, args: &Args, stats: &mut CompressionStats) {
+fn create_chunks(content: &str, args: &Args, profile: &models::Profile, stats: &mut CompressionStats) {
     let lines: Vec<&str> = content.lines().collect();
     let total_lines = lines.len();
-    let chunk_size = args.max_lines;
-    let total_chunks = (total_lines + chunk_size - 1) / chunk_size;
+    let chunk_size = args.max_lines.unwrap_or(profile.chunk_lines);
+    let total_chunks = total_lines.div_ceil(chunk_size);
     
-    println!("📦 Creating {} chunks of ~{} lines each...", total_chunks, chunk_size);
+    eprintln!("📦 Creating {} chunks of ~{} lines each...", total_chunks, chunk_size);
+    
+    // Which chunk each file record starts in, for bundle link references
+    let mut record_chunks = std::collections::HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(record) = line.strip_prefix("F:") {
+            let name = record.split('|').next().unwrap_or(record);
+            record_chunks.entry(name.to_string()).or_insert(i / chunk_size + 1);
+        }
+    }
     
     for chunk_num in 0..total_chunks {
         let start_idx = chunk_num * chunk_size;
         let end_idx = std::cmp::min(start_idx + chunk_size, total_lines);
-        let chunk_lines = &lines[start_idx..end_idx];
+        let chunk_text = refs::add_chunk_numbers(&lines[start_idx..end_idx].join("\n"), &record_chunks);
+        let chunk_lines: Vec<&str> = chunk_text.lines().collect();
         
         let mut chunk_content = String::new();
         
@@ -357,7 +1268,7 @@ fn create_chunks(content: &str, args: &Args, stats: &mut CompressionStats) {
                 };
                 chunk_content.push_str(&format!(" | NEXT:{}", next_chunk_name));
             }
-            chunk_content.push_str("\n");
+            chunk_content.push('\n');
             chunk_content.push_str(&chunk_lines.join("\n"));
             chunk_content.push_str(&format!("\n---\nCHUNK_END | Lines:{} | Est.tokens:{}", 
                                            chunk_lines.len(), 
@@ -371,13 +1282,13 @@ fn create_chunks(content: &str, args: &Args, stats: &mut CompressionStats) {
             format!("{}_chunk_{}.{}", args.output, chunk_num + 1, if args.format == "vrd" { "vrd" } else { "md" })
         };
         
-        match fs::write(&chunk_filename, &chunk_content) {
+        match write_output(&chunk_filename, &chunk_content, args) {
             Ok(()) => {
-                println!("  ✅ Created {}", chunk_filename);
+                eprintln!("  ✅ Created {}", if args.output == "-" { "chunk on stdout" } else { &chunk_filename });
                 stats.compressed_size += chunk_content.len();
                 stats.compressed_lines += chunk_content.lines().count();
             }
-            Err(e) => println!("  ❌ Error writing {}: {}", chunk_filename, e),
+            Err(e) => eprintln!("  ❌ Error writing {}: {}", chunk_filename, e),
         }
     }
     
@@ -391,17 +1302,31 @@ fn write_single_file(content: &str, args: &Args, stats: &mut CompressionStats) {
         format!("{}.md", args.output)
     };
     
-    match fs::write(&output_filename, content) {
+    match write_output(&output_filename, content, args) {
         Ok(()) => {
-            println!("✅ Successfully compressed and wrote to {}", output_filename);
+            eprintln!("✅ Successfully compressed and wrote to {}", if args.output == "-" { "stdout" } else { &output_filename });
             stats.compressed_size = content.len();
             stats.compressed_lines = content.lines().count();
         }
-        Err(e) => println!("❌ Error writing output: {}", e),
+        Err(e) => eprintln!("❌ Error writing output: {}", e),
     }
 }
 
-fn compress_content(content: &str, level: &str, model: &str, ai_mode: bool, no_emojis: bool) -> String {
+fn write_output(filename: &str, content: &str, args: &Args) -> std::io::Result<()> {
+    if args.output == "-" {
+        // Chunks are streamed back to back, each ending on its own line
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(content.as_bytes())?;
+        if !content.ends_with('\n') {
+            stdout.write_all(b"\n")?;
+        }
+        stdout.flush()
+    } else {
+        fs::write(filename, content)
+    }
+}
+
+fn compress_content(content: &str, passes: &[&dyn passes::Pass], profile: &models::Profile, no_emojis: bool) -> String {
     let mut compressed = content.to_string();
     
     // Remove emojis if requested (do this early to save processing)
@@ -409,35 +1334,8 @@ fn compress_content(content: &str, level: &str, model: &str, ai_mode: bool, no_e
         compressed = remove_emojis(&compressed);
     }
     
-    // Always apply basic compression
-    compressed = remove_excessive_whitespace(&compressed);
-    compressed = remove_empty_lines(&compressed);
-    compressed = compress_headers_aggressively(&compressed);
-    compressed = compress_formatting(&compressed);
-    
-    // Apply level-based compression
-    match level {
-        "medium" | "high" | "extreme" => {
-            compressed = compress_code_blocks(&compressed, model);
-            compressed = compress_lists_aggressively(&compressed);
-            compressed = remove_fluff_words(&compressed);
-        }
-        _ => {} // low level - just basic
-    }
-    
-    if level == "high" || level == "extreme" {
-        compressed = compress_sentences(&compressed);
-        compressed = remove_redundant_phrases(&compressed);
-    }
-    
-    if level == "extreme" || ai_mode {
-        compressed = apply_extreme_ai_compression(&compressed);
-    }
-    
-    // Apply model-specific optimizations
-    compressed = apply_model_optimizations(&compressed, model);
-    
-    compressed
+    // Level passes (see passes.rs), model-specific optimizations last
+    passes::run(passes, &compressed, profile)
 }
 
 fn apply_extreme_ai_compression(content: &str) -> String {
@@ -469,53 +1367,102 @@ fn apply_extreme_ai_compression(content: &str) -> String {
     result
 }
 
-fn apply_model_optimizations(content: &str, model: &str) -> String {
-    match model {
-        "copilot" => {
-            // Copilot: prioritize code and add file type hints
-            let mut result = content.to_string();
-            // Add more aggressive code compression for copilot
-            result = prioritize_code_content(&result);
-            result
-        }
-        "gpt" => {
+fn apply_model_optimizations(content: &str, profile: &models::Profile) -> String {
+    match profile.optimize.as_str() {
+        "sections" => {
             // GPT: add more explicit structure markers
             let re_sections = Regex::new(r"H(\d):(.+)").unwrap();
             re_sections.replace_all(content, "SECTION_L$1:$2").to_string()
         }
-        "claude" => {
-            // Claude: can handle more complex nested structures
-            content.to_string() // Claude handles the current format well
-        }
-        _ => content.to_string(),
+        _ => content.to_string(), // Claude handles the current format well; copilot gets the code-focus pass
+    }
+}
+
+fn apply_code_focus(content: &str, profile: &models::Profile) -> String {
+    // Only code-focused profiles (copilot) trade prose for code
+    if profile.optimize == "code" {
+        prioritize_code_content(content)
+    } else {
+        content.to_string()
     }
 }
 
 fn prioritize_code_content(content: &str) -> String {
-    // Move all code blocks to the beginning of sections
-    // This is a simplified implementation - you could make this much more sophisticated
-    content.to_string()
+    // Code, signatures and tables stay at full fidelity; each paragraph and
+    // list item around them is cut to its first sentence
+    let re_labelled = Regex::new(r"^[A-Z][A-Z0-9+#_-]*(\([^)]*\))?:").unwrap(); // H1:, CODE(rs):, RUST:, TBL...
+    let re_item = Regex::new(r"^([-*+•№☐☑]|\d+[.)])\s*").unwrap();
+    let re_sentence_end = Regex::new(r"[.!?](\s+[A-Z\[(→]|\s*$)").unwrap(); // VRD arrows replace "Then"
+    let in_code = fences::code_lines(content);
+    
+    let mut result = Vec::new();
+    let mut sentence_done = false; // The current paragraph or item already has its first sentence
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let keep_whole = in_code[i]
+            || trimmed.is_empty()
+            || trimmed.starts_with('#')
+            || trimmed.starts_with("TBL[")
+            || re_labelled.is_match(trimmed)
+            || line.contains(['\u{E002}', '\u{E004}']) // VRD code and heading placeholders
+            || line.contains('`') // Inline code: signatures, parameter docs
+            || line.contains('|'); // Table rows
+        if keep_whole {
+            result.push(line.to_string());
+            sentence_done = false;
+            continue;
+        }
+        
+        if re_item.is_match(trimmed) {
+            sentence_done = false;
+        } else if sentence_done {
+            continue; // Rest of a paragraph whose first sentence is kept
+        }
+        match re_sentence_end.find(line) {
+            Some(end) => {
+                result.push(line[..end.start() + 1].to_string());
+                sentence_done = true;
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+    
+    result.join("\n")
 }
 
-fn remove_duplicate_content(all_files_content: Vec<(String, String, std::path::PathBuf)>, show_stats: bool) -> Vec<(String, String, std::path::PathBuf)> {
+fn fence_languages(content: &str) -> Vec<String> {
+    // Language tags of fenced code blocks, in order of first use
+    let mut languages: Vec<String> = Vec::new();
+    for block in fences::blocks(content) {
+        let lang = block.lang.split([',', '{']).next().unwrap_or("");
+        if !lang.is_empty() && !languages.iter().any(|l| l == lang) {
+            languages.push(lang.to_string());
+        }
+    }
+    languages
+}
+
+fn remove_duplicate_content(all_files_content: Vec<SourceFile>, show_stats: bool) -> Vec<SourceFile> {
     let mut seen_paragraphs = std::collections::HashSet::new();
     let mut deduplicated = Vec::new();
     let mut duplicates_removed = 0;
     
-    for (filename, content, path) in all_files_content {
-        let paragraphs: Vec<&str> = content.split('\n').collect();
+    for mut file in all_files_content {
+        let paragraphs: Vec<&str> = file.content.split('\n').collect();
         let mut unique_paragraphs = Vec::new();
+        let in_code = fences::code_lines(&file.content);
         
-        for paragraph in paragraphs {
+        for (i, paragraph) in paragraphs.into_iter().enumerate() {
             let trimmed = paragraph.trim();
-            if trimmed.len() > 30 {
+            // Code blocks are kept whole; repeated ones become snippets instead
+            if trimmed.len() > 30 && !in_code.get(i).copied().unwrap_or(false) {
                 if !seen_paragraphs.contains(trimmed) {
                     seen_paragraphs.insert(trimmed.to_string());
                     unique_paragraphs.push(paragraph);
                 } else {
                     duplicates_removed += 1;
                     if show_stats {
-                        println!("    🔄 Removed duplicate from {}: {:.50}...", filename, trimmed);
+                        eprintln!("    🔄 Removed duplicate from {}: {:.50}...", file.name, trimmed);
                     }
                 }
             } else {
@@ -523,11 +1470,12 @@ fn remove_duplicate_content(all_files_content: Vec<(String, String, std::path::P
             }
         }
         
-        deduplicated.push((filename, unique_paragraphs.join("\n"), path));
+        file.content = unique_paragraphs.join("\n");
+        deduplicated.push(file);
     }
     
     if duplicates_removed > 0 {
-        println!("   ✂️  Removed {} duplicate paragraphs", duplicates_removed);
+        eprintln!("   ✂️  Removed {} duplicate paragraphs", duplicates_removed);
     }
     
     deduplicated
@@ -580,34 +1528,26 @@ fn compress_formatting(content: &str) -> String {
     result
 }
 
-fn compress_code_blocks(content: &str, model: &str) -> String {
+fn compress_code_blocks(content: &str, profile: &models::Profile) -> String {
     let re_code_block = Regex::new(r"```(\w+)?\n([\s\S]*?)```").unwrap();
     
     re_code_block.replace_all(content, |caps: &regex::Captures| {
         let lang = caps.get(1).map_or("", |m| m.as_str());
         let code = caps.get(2).map_or("", |m| m.as_str());
         
-        let compressed_code = code.lines()
-            .filter(|line| !line.trim().is_empty())
-            .collect::<Vec<_>>()
-            .join("\n");
-            
-        match model {
-            "copilot" => {
-                // More aggressive compression for copilot
-                if lang.is_empty() {
-                    format!("CODE:{}", compressed_code.replace('\n', " | "))
-                } else {
-                    format!("{}:{}", lang.to_uppercase(), compressed_code.replace('\n', " | "))
-                }
-            }
-            _ => {
-                if lang.is_empty() {
-                    format!("CODE:{}", compressed_code.replace('\n', "|"))
-                } else {
-                    format!("CODE({}):{}", lang, compressed_code.replace('\n', "|"))
-                }
-            }
+        let compressed_code = minify::minify(code, lang).unwrap_or_else(|| {
+            code.lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        
+        let joined = compressed_code.replace('\n', &profile.delimiter);
+        match profile.code_style.as_str() {
+            // Language as the label, e.g. for copilot
+            "language" if !lang.is_empty() => format!("{}:{}", lang.to_uppercase(), joined),
+            "tagged" if !lang.is_empty() => format!("CODE({}):{}", lang, joined),
+            _ => format!("CODE:{}", joined),
         }
     }).to_string()
 }
@@ -655,7 +1595,7 @@ fn remove_redundant_phrases(content: &str) -> String {
     result
 }
 
-fn print_final_stats(stats: &CompressionStats, show_detailed: bool) {
+fn print_final_stats(stats: &CompressionStats, show_detailed: bool, profile: &models::Profile) {
     let compression_ratio = if stats.original_size > 0 {
         (1.0 - (stats.compressed_size as f64 / stats.original_size as f64)) * 100.0
     } else {
@@ -668,42 +1608,42 @@ fn print_final_stats(stats: &CompressionStats, show_detailed: bool) {
         0.0
     };
     
-    println!("\n📊 COMPRESSION RESULTS:");
+    eprintln!("\n📊 COMPRESSION RESULTS:");
     
     if stats.chunks_created > 0 {
-        println!("   Created {} chunks", stats.chunks_created);
+        eprintln!("   Created {} chunks", stats.chunks_created);
     }
     
     if show_detailed {
-        println!("   Original:   {} lines, {} chars", stats.original_lines, stats.original_size);
-        println!("   Compressed: {} lines, {} chars", stats.compressed_lines, stats.compressed_size);
-        println!("   Line compression: {:.1}%", line_compression_ratio);
-        println!("   Char compression: {:.1}%", compression_ratio);
+        eprintln!("   Original:   {} lines, {} chars", stats.original_lines, stats.original_size);
+        eprintln!("   Compressed: {} lines, {} chars", stats.compressed_lines, stats.compressed_size);
+        eprintln!("   Line compression: {:.1}%", line_compression_ratio);
+        eprintln!("   Char compression: {:.1}%", compression_ratio);
         
-        let original_tokens = stats.original_size / 4;
-        let compressed_tokens = stats.compressed_size / 4;
-        println!("   Est. tokens: {} → {} (saved ~{})", 
-                 original_tokens, compressed_tokens, original_tokens.saturating_sub(compressed_tokens));
+        let original_tokens = profile.estimate_tokens(stats.original_size);
+        let compressed_tokens = profile.estimate_tokens(stats.compressed_size);
+        eprintln!("   Est. tokens ({}): {} → {} (saved ~{})", 
+                 profile.tokenizer, original_tokens, compressed_tokens, original_tokens.saturating_sub(compressed_tokens));
     } else {
-        println!("   {} chars → {} chars ({:.1}% reduction)", 
+        eprintln!("   {} chars → {} chars ({:.1}% reduction)", 
                  stats.original_size, stats.compressed_size, compression_ratio);
-        println!("   {} lines → {} lines ({:.1}% reduction)", 
+        eprintln!("   {} lines → {} lines ({:.1}% reduction)", 
                  stats.original_lines, stats.compressed_lines, line_compression_ratio);
     }
 }
 
 
-fn generate_vrd_content(all_files_content: &[(String, String, std::path::PathBuf)], args: &Args, original_stats: &CompressionStats) -> String {
+fn generate_vrd_content(all_files_content: &[SourceFile], header_extras: &str, args: &Args, selection: &passes::Selection, profile: &models::Profile, original_stats: &CompressionStats) -> String {
     let mut vrd_files = Vec::new();
     
     // Process each file into VRD format
-    for (filename, content, path) in all_files_content {
-        let vrd_file = process_file_for_vrd(filename, content, args, path);
+    for file in all_files_content {
+        let vrd_file = process_file_for_vrd(file, args, selection, profile);
         vrd_files.push(vrd_file);
     }
     
     // Build VRD content first to calculate accurate size
-    let vrd_content = build_vrd_output(&vrd_files, args);
+    let vrd_content = build_vrd_output(&vrd_files, header_extras, args);
     
     // Calculate actual compression stats
     let compressed_size = vrd_content.len();
@@ -739,27 +1679,26 @@ fn update_vrd_metadata(content: &str, metadata: &VrdMetadata) -> String {
     )
 }
 
-fn process_file_for_vrd(filename: &str, content: &str, args: &Args, file_path: &std::path::Path) -> VrdFile {
-    // Get real file modification time
-    let modified_time = if let Ok(metadata) = file_path.metadata() {
-        if let Ok(modified) = metadata.modified() {
-            DateTime::<Utc>::from(modified)
-        } else {
-            Utc::now()
-        }
-    } else {
-        Utc::now()
-    };
+fn process_file_for_vrd(file: &SourceFile, args: &Args, selection: &passes::Selection, profile: &models::Profile) -> VrdFile {
+    let content = file.content.as_str();
 
     let mut vrd_file = VrdFile {
-        name: filename.to_string(),
-        modified: modified_time, // Use actual file time
+        name: file.name.clone(),
+        modified: file.modified, // File mtime, or last commit with --git
+        created: file.created,
+        authors: file.authors.clone(),
         size: content.len(),
         lines: content.lines().count(),
-        tags: extract_enhanced_tags_from_content(content),
-        headers: extract_headers_for_vrd(content, args.no_emojis),
+        tags: merge_tags(file.frontmatter.as_ref().map_or(&[][..], |fm| &fm.tags), extract_enhanced_tags_from_content(content)),
+        headers: Vec::new(),
         content: String::new(),
         code_blocks: Vec::new(),
+        change: file.change.clone(),
+        diff: file.diff.clone(),
+        section: file.section.clone(),
+        title: file.frontmatter.as_ref().and_then(|fm| fm.title.clone()),
+        priority: file.priority.clone(),
+        languages: if profile.optimize == "code" { fence_languages(content) } else { Vec::new() },
     };
     
     // Process content through compression pipeline
@@ -769,18 +1708,34 @@ fn process_file_for_vrd(filename: &str, content: &str, args: &Args, file_path: &
         processed_content = remove_emojis(&processed_content);
     }
     
-    vrd_file.code_blocks = extract_and_compress_code_blocks(&processed_content);
-    processed_content = apply_vrd_compression(&processed_content, &args.level);
+    // Inline code is parked behind placeholders while the prose is compressed
+    let mut inline_code = Vec::new();
+    if args.code_layout == "inline" {
+        processed_content = stash_code_blocks(&processed_content, &mut inline_code);
+    } else {
+        vrd_file.code_blocks = extract_and_compress_code_blocks(&processed_content);
+    }
+    
+    // Headings become outline entries; their placeholders mark where each section starts
+    let (outlined, headers) = outline_headings(&processed_content, args.no_emojis);
+    processed_content = outlined;
+    vrd_file.headers = headers;
+    processed_content = directives::compress_regions(&processed_content, &args.level, |text, level| {
+        apply_vrd_compression(text, &selection.passes("vrd", level, args.ai_mode), profile)
+    });
+    processed_content = directives::restore_verbatim(&processed_content, &file.verbatim);
+    for (i, block) in inline_code.iter().enumerate() {
+        processed_content = processed_content.replace(&format!("\u{E002}{}\u{E003}", i), block);
+    }
     vrd_file.content = processed_content;
     vrd_file
 }
 
-fn apply_vrd_compression(content: &str, level: &str) -> String {
+fn apply_vrd_compression(content: &str, passes: &[&dyn passes::Pass], profile: &models::Profile) -> String {
     let mut result = content.to_string();
     
     // Remove code blocks (they're handled separately)
-    let re_code_block = regex::Regex::new(r"```[\s\S]*?```").unwrap();
-    result = re_code_block.replace_all(&result, "").to_string();
+    result = fences::replace_blocks(&result, |_| Some(String::new()));
     
     // Remove headers (they're in the H: field) - apply line by line
     result = result
@@ -789,30 +1744,8 @@ fn apply_vrd_compression(content: &str, level: &str) -> String {
         .collect::<Vec<_>>()
         .join("\n");
     
-    // Apply standard compression
-    result = remove_excessive_whitespace(&result);
-    result = remove_empty_lines(&result);
-    
-    // VRD-specific optimizations
-    result = apply_arrow_notation(&result);
-    result = apply_vrd_abbreviations(&result);
-    result = compress_vrd_lists(&result);
-    result = compress_vrd_sentences(&result);
-    
-    // NEW v2.3.1 optimizations for improved compression
-    result = optimize_checkboxes(&result);
-    result = optimize_emphasis(&result);
-    // Note: optimize_code_blocks is handled separately in extract_and_compress_code_blocks
-    
-    match level {
-        "high" | "extreme" => {
-            result = apply_extreme_vrd_compression(&result);
-            result = apply_mathematical_notation(&result);
-        }
-        _ => {}
-    }
-    
-    result
+    // Level passes (see passes.rs)
+    passes::run(passes, &result, profile)
 }
 
 fn compress_vrd_lists(content: &str) -> String {
@@ -856,82 +1789,48 @@ fn compress_vrd_sentences(content: &str) -> String {
     result
 }
 
-fn extract_headers_for_vrd(content: &str, no_emojis: bool) -> Vec<String> {
+fn outline_headings(content: &str, no_emojis: bool) -> (String, Vec<(usize, String, String)>) {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
     let mut headers = Vec::new();
     
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('#') {
-            // Extract header text, removing markdown symbols
-            let mut header_text = trimmed
-                .trim_start_matches('#')
-                .trim()
-                .to_string();
-            
-            // Apply emoji removal if enabled
-            if no_emojis {
-                header_text = remove_emojis(&header_text);
-            }
-            
-            headers.push(header_text);
+    for heading in refs::headings(content) {
+        // The anchor stays the one links use, whatever happens to the title
+        let mut header_text = heading.title;
+        if no_emojis {
+            header_text = remove_emojis(&header_text).trim().to_string();
         }
+        lines[heading.line] = format!("\u{E004}{}\u{E005}", headers.len());
+        headers.push((heading.level, header_text, heading.anchor));
     }
     
-    headers
+    (lines.join("\n"), headers)
+}
+
+fn stash_code_blocks(content: &str, blocks: &mut Vec<String>) -> String {
+    // Each fenced block becomes one compact line: ```lang code→code→...
+    fences::replace_blocks(content, |block| {
+        blocks.push(format!("```{} {}", block.lang, compress_code_for_vrd(&block.body.join("\n"), &block.lang)));
+        Some(format!("\u{E002}{}\u{E003}", blocks.len() - 1))
+    })
 }
 
 fn extract_and_compress_code_blocks(content: &str) -> Vec<String> {
-    // First apply the new code block optimization
-    let optimized_content = optimize_code_blocks(content);
-    
-    let re_code_block = regex::Regex::new(r"```(\w+)?\n([\s\S]*?)```").unwrap();
-    let mut code_blocks = Vec::new();
-    
-    for cap in re_code_block.captures_iter(&optimized_content) {
-        let lang = cap.get(1).map_or("", |m| m.as_str());
-        let code = cap.get(2).map_or("", |m| m.as_str());
-        
-        // Compress code using arrow notation
-        let compressed_code = compress_code_for_vrd(code, lang);
-        code_blocks.push(compressed_code);
-    }
-    
-    // Also handle the new compact formats we created
-    let re_compact_code = regex::Regex::new(r"CODE\(([^)]+)\)→([^→]+)→CODE\(/[^)]+\)").unwrap();
-    for cap in re_compact_code.captures_iter(&optimized_content) {
-        let compressed = cap.get(0).map_or("", |m| m.as_str()).to_string();
-        code_blocks.push(compressed);
-    }
-    
-    code_blocks
+    // Appendix layout: one X: record per fenced block, in document order
+    fences::blocks(content)
+        .iter()
+        .map(|block| compress_code_for_vrd(&block.body.join("\n"), &block.lang))
+        .collect()
 }
 
-fn compress_code_for_vrd(code: &str, _lang: &str) -> String {
-    // Ultra-aggressive code compression for VRD
-    let compressed = code
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            line.trim()
-                // Replace common patterns with arrows
-                .replace(" => ", "→")
-                .replace(" -> ", "→")
-                .replace("return ", "→")
-                .replace("async function ", "async FN ")
-                .replace("function ", "FN ")
-                .replace("const ", "")
-                .replace("let ", "")
-                .replace("var ", "")
-                // Remove unnecessary spaces
-                .replace("( ", "(")
-                .replace(" )", ")")
-                .replace("{ ", "{")
-                .replace(" }", "}")
-        })
-        .collect::<Vec<_>>()
-        .join("→");
-    
-    compressed
+fn compress_code_for_vrd(code: &str, lang: &str) -> String {
+    // Known languages are minified safely; others keep their lines as written
+    let code = minify::minify(code, lang).unwrap_or_else(|| {
+        code.lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    code.lines().collect::<Vec<_>>().join("→")
 }
 
 fn apply_arrow_notation(content: &str) -> String {
@@ -1157,7 +2056,7 @@ fn update_vrd_chunk_header(content: String, chunk_num: usize, total_chunks: usiz
     }
 }
 
-fn build_vrd_output(vrd_files: &[VrdFile], args: &Args) -> String {
+fn build_vrd_output(vrd_files: &[VrdFile], header_extras: &str, args: &Args) -> String {
     let mut output = String::new();
     
     // Header (metadata will be updated later)
@@ -1189,43 +2088,95 @@ fn build_vrd_output(vrd_files: &[VrdFile], args: &Args) -> String {
         if i > 0 { output.push(','); }
         output.push_str(&format!("{}={}", abbrev, full));
     }
-    output.push_str("}\n---\n");
+    output.push_str("}\n");
+    output.push_str(header_extras);
+    output.push_str("---\n");
     
     // File contents
     for (i, file) in vrd_files.iter().enumerate() {
         if i > 0 { output.push('\n'); }
         
         // File header
-        output.push_str(&format!(
-            "F:{}|D:{}|S:{}|L:{}|T:{}\n",
-            file.name,
-            file.modified.format("%Y-%m-%dT%H:%M:%SZ"),
-            file.size,
-            file.lines,
-            file.tags.join(",")
-        ));
-        
-        // Headers
-        if !file.headers.is_empty() {
-            output.push_str(&format!("H:{}\n", file.headers.join(",")));
+        output.push_str(&format!("F:{}", file.name));
+        if let Some(title) = &file.title {
+            output.push_str(&format!("|TI:{}", title));
+        }
+        output.push_str(&format!("|D:{}", file.modified.format("%Y-%m-%dT%H:%M:%SZ")));
+        if let Some(created) = file.created {
+            output.push_str(&format!("|CR:{}", created.format("%Y-%m-%dT%H:%M:%SZ")));
         }
+        output.push_str(&format!("|S:{}|L:{}|T:{}", file.size, file.lines, file.tags.join(",")));
+        if !file.section.is_empty() {
+            output.push_str(&format!("|SEC:{}", file.section.join("›")));
+        }
+        if !file.authors.is_empty() {
+            output.push_str(&format!("|A:{}", file.authors.join(",")));
+        }
+        if let Some(priority) = &file.priority {
+            output.push_str(&format!("|P:{}", priority));
+        }
+        if !file.languages.is_empty() {
+            output.push_str(&format!("|LANG:{}", file.languages.join(",")));
+        }
+        if let Some(change) = &file.change {
+            output.push_str(&format!("|Δ:{}", change));
+        }
+        output.push('\n');
         
-        // Content
-        if !file.content.trim().is_empty() {
-            output.push_str(&format!("C:{}\n", file.content.trim()));
+        // Content, one C: block per section under its H<level>:title#anchor line
+        let mut section: Vec<&str> = Vec::new();
+        let flush = |section: &mut Vec<&str>, output: &mut String| {
+            let text = section.join("\n");
+            if !text.trim().is_empty() {
+                output.push_str(&format!("C:{}\n", text.trim()));
+            }
+            section.clear();
+        };
+        for line in file.content.lines() {
+            let heading = line
+                .trim()
+                .strip_prefix('\u{E004}')
+                .and_then(|rest| rest.strip_suffix('\u{E005}'))
+                .and_then(|index| index.parse::<usize>().ok())
+                .and_then(|index| file.headers.get(index));
+            match heading {
+                Some((level, title, anchor)) => {
+                    flush(&mut section, &mut output);
+                    output.push_str(&format!("H{}:{}#{}\n", level, title, anchor));
+                }
+                None => section.push(line),
+            }
         }
+        flush(&mut section, &mut output);
         
         // Code blocks
         for code_block in &file.code_blocks {
             output.push_str(&format!("X:{}\n", code_block));
         }
         
+        // Diff hunks against --since
+        for line in &file.diff {
+            output.push_str(&format!("Δ{}\n", line));
+        }
+        
         output.push_str("|\n");
     }
     
     output
 }
 
+fn merge_tags(declared: &[String], detected: Vec<String>) -> Vec<String> {
+    // Author-declared tags come first and are never truncated
+    let mut tags = declared.to_vec();
+    let limit = tags.len().max(5);
+    for tag in detected {
+        if tags.len() < limit && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
 fn extract_enhanced_tags_from_content(content: &str) -> Vec<String> {
     let mut tags = std::collections::HashSet::new();
     let content_lower = content.to_lowercase();
@@ -1303,49 +2254,109 @@ fn optimize_emphasis(content: &str) -> String {
     result
 }
 
-fn optimize_code_blocks(content: &str) -> String {
-    let mut result = content.to_string();
-    
-    // Optimize multi-line code blocks with language specification
-    let re_code_with_lang = Regex::new(r"```(\w+)\n([\s\S]*?)```").unwrap();
-    result = re_code_with_lang.replace_all(&result, |caps: &regex::Captures| {
-        let lang = caps.get(1).map_or("", |m| m.as_str());
-        let code = caps.get(2).map_or("", |m| m.as_str());
-        
-        // Compress code lines and use compact format
-        let compressed_lines: Vec<&str> = code
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .collect();
-        
-        if compressed_lines.len() <= 1 {
-            // Single line: use inline format
-            format!("CODE({}): {}", lang.to_uppercase(), compressed_lines.join(""))
-        } else {
-            // Multiple lines: use compact block format
-            format!("CODE({})→{}→CODE(/{}) ", 
-                   lang.to_uppercase(), 
-                   compressed_lines.join("→"), 
-                   lang.to_uppercase())
-        }
-    }).to_string();
-    
-    // Optimize code blocks without language specification
-    let re_code_no_lang = Regex::new(r"```\n([\s\S]*?)```").unwrap();
-    result = re_code_no_lang.replace_all(&result, |caps: &regex::Captures| {
-        let code = caps.get(1).map_or("", |m| m.as_str());
-        
-        let compressed_lines: Vec<&str> = code
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .collect();
-        
-        if compressed_lines.len() <= 1 {
-            format!("CODE: {}", compressed_lines.join(""))
-        } else {
-            format!("CODE→{}→/CODE", compressed_lines.join("→"))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    // A tree of markdown files, each holding a heading with its own path
+    fn tree(name: &str, files: &[&str]) -> TempDir {
+        let dir = TempDir::new(name);
+        for file in files {
+            dir.write(file, &format!("# {}\n", file));
         }
-    }).to_string();
-    
-    result
-}
\ No newline at end of file
+        dir
+    }
+
+    fn identities(inputs: &[String]) -> Vec<String> {
+        let mut names: Vec<String> = collect_input_files(inputs).into_iter().map(|f| f.relative).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn single_root_uses_paths_relative_to_it() {
+        let tree = tree("single-root", &["docs/intro.md", "docs/guide/setup.md"]);
+        assert_eq!(identities(&[tree.input("docs")]), ["guide/setup.md", "intro.md"]);
+    }
+
+    #[test]
+    fn glob_expanding_to_several_roots_keeps_root_names() {
+        let tree = tree("glob-roots", &["crates/a/README.md", "crates/b/README.md", "crates/b/docs/intro.md"]);
+        assert_eq!(identities(&[tree.input("crates/*")]), ["a/README.md", "b/README.md", "b/docs/intro.md"]);
+    }
+
+    #[test]
+    fn glob_of_files_gets_shortest_unique_suffix() {
+        let tree = tree("glob-files", &["crates/a/README.md", "crates/b/README.md", "crates/c/CHANGES.md"]);
+        assert_eq!(identities(&[tree.input("crates/*/*.md")]), ["CHANGES.md", "a/README.md", "b/README.md"]);
+    }
+
+    #[test]
+    fn same_named_roots_never_show_absolute_paths() {
+        let tree = tree("same-roots", &["one/docs/intro.md", "two/docs/intro.md"]);
+        let names = identities(&[tree.input("one/docs"), tree.input("two/docs")]);
+        assert_eq!(names, ["one/docs/intro.md", "two/docs/intro.md"]);
+    }
+
+    #[test]
+    fn code_focus_shortens_prose_but_keeps_every_paragraph() {
+        let content = "Intro sentence. More detail here,\nwrapping on. And more.\n\n- First item. Its detail.\n- Second item\n\nCall `parse(s: &str)` to start. It returns a Doc.\n```rust\nfn main() {}\n```\nVersion 1.2 works. Yes.";
+        assert_eq!(
+            prioritize_code_content(content),
+            "Intro sentence.\n\n- First item.\n- Second item\n\nCall `parse(s: &str)` to start. It returns a Doc.\n```rust\nfn main() {}\n```\nVersion 1.2 works."
+        );
+    }
+
+    #[test]
+    fn fence_languages_follow_first_use() {
+        let content = "```rust\n```\n````md\n```bash\n```\n````\n~~~Bash\n~~~\n```rust\n```\n";
+        assert_eq!(fence_languages(content), ["rust", "md", "bash"]);
+    }
+
+    fn vrd_file(content: &str, extra_args: &[&str]) -> VrdFile {
+        let args = Args::parse_from(["verdant", "--input", ".", "--format", "vrd"].iter().chain(extra_args));
+        let profile = models::load(&[]).and_then(|profiles| models::find(&profiles, "claude")).unwrap();
+        let file = SourceFile { name: "a.md".to_string(), content: content.to_string(), ..Default::default() };
+        process_file_for_vrd(&file, &args, &passes::Selection::default(), &profile)
+    }
+
+    #[test]
+    fn appendix_keeps_every_fenced_block() {
+        let content = "# Setup\nRun it:\n```bash\nnpm install\nnpm test\n```\n```json\n{\"a\": 1}\n```\n````md\n```\nnested\n```\n````\nDone.\n";
+        let file = vrd_file(content, &[]);
+        assert_eq!(file.code_blocks.len(), fences::blocks(content).len());
+        assert_eq!(file.code_blocks[0], "npm install→npm test");
+        assert_eq!(file.code_blocks[2], "```→nested→```");
+        assert!(!file.content.contains("nested") && file.content.contains("Done."));
+    }
+
+    #[test]
+    fn inline_layout_keeps_blocks_in_place() {
+        let file = vrd_file("Before.\n````bash\n```\necho hi\n```\n````\nAfter.\n", &["--code-layout", "inline"]);
+        assert!(file.code_blocks.is_empty());
+        assert!(file.content.contains("Before.\n```bash ```→echo hi→```\nAfter."), "{}", file.content);
+    }
+
+    #[test]
+    fn outline_skips_mixed_fences_and_keeps_link_anchors() {
+        let content = "~~~text\n```\n# not a heading\n~~~\n## After section\n# 🚀 Quick Start\n#hashtag";
+        let (outlined, headers) = outline_headings(content, true);
+        assert_eq!(outlined, "~~~text\n```\n# not a heading\n~~~\n\u{E004}0\u{E005}\n\u{E004}1\u{E005}\n#hashtag");
+        assert_eq!(headers, [(2, "After section".to_string(), "after-section".to_string()), (1, "Quick Start".to_string(), "-quick-start".to_string())]);
+    }
+
+    #[test]
+    fn dedup_never_touches_code_after_mixed_fences() {
+        let repeated = "This paragraph appears in both of the files.";
+        let code = "    let value = compute_the_answer(42);";
+        let files = ["a.md", "b.md"].map(|name| SourceFile {
+            name: name.to_string(),
+            content: format!("~~~text\n```\n~~~\n{}\n```rust\n{}\n```", repeated, code),
+            ..Default::default()
+        });
+        let deduplicated = remove_duplicate_content(files.into(), false);
+        assert_eq!(deduplicated[1].content, format!("~~~text\n```\n~~~\n```rust\n{}\n```", code));
+    }
+}
+
@@ -0,0 +1,454 @@
+// Per-language code minification for fenced blocks, keyed by the fence tag.
+// Only what is safe for the language is removed: comments (outside string,
+// char and regex literals), blank lines and runs of spaces. Python keeps its
+// indentation, and multi-line literals and shell heredocs are kept exactly as
+// written.
+// Unknown languages get None and are left as written.
+
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    raw_quotes: &'static [char], // No escapes inside, may span lines (Go `...`, shell '...')
+    triple_quotes: bool,         // """...""" spans lines, with no escapes
+    hashed_raw: bool,            // Rust: r"..." and r#"..."# span lines, with no escapes
+    char_literals: bool,         // 'x' is a char literal, a lone ' is a lifetime/label
+    regex_literals: bool,        // JS: / starts a regex where an operand is expected
+    multiline_quotes: bool,      // Shell: "..." may span lines
+    hash_after_space: bool,      // Shell: # starts a comment only at the start of a word
+    heredocs: bool,              // Shell: <<EOF bodies are data up to the EOF line
+    keep_indent: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    raw_quotes: &[],
+    triple_quotes: false,
+    hashed_raw: false,
+    char_literals: true,
+    regex_literals: false,
+    multiline_quotes: false,
+    hash_after_space: false,
+    heredocs: false,
+    keep_indent: false,
+};
+
+const RUST: Syntax = Syntax {
+    hashed_raw: true,
+    ..C_LIKE
+};
+
+const GO: Syntax = Syntax {
+    raw_quotes: &['`'],
+    ..C_LIKE
+};
+
+// Java, Kotlin, Swift, C# and Scala add """ multi-line strings
+const TRIPLE_QUOTED: Syntax = Syntax {
+    triple_quotes: true,
+    ..C_LIKE
+};
+
+const JS_LIKE: Syntax = Syntax {
+    quotes: &['"', '\'', '`'],
+    char_literals: false,
+    regex_literals: true,
+    ..C_LIKE
+};
+
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    quotes: &['"', '\''],
+    char_literals: false,
+    ..C_LIKE
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    triple_quotes: true,
+    char_literals: false,
+    keep_indent: true,
+    ..C_LIKE
+};
+
+const RUBY: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    char_literals: false,
+    ..C_LIKE
+};
+
+const SHELL: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"'],
+    raw_quotes: &['\''],
+    char_literals: false,
+    multiline_quotes: true,
+    hash_after_space: true,
+    heredocs: true,
+    ..C_LIKE
+};
+
+const SQL: Syntax = Syntax {
+    line_comments: &["--"],
+    quotes: &['\'', '"'],
+    char_literals: false,
+    ..C_LIKE
+};
+
+// Words after which a / starts a regex rather than dividing
+const REGEX_KEYWORDS: [&str; 13] =
+    ["return", "typeof", "instanceof", "case", "do", "else", "in", "of", "new", "delete", "void", "throw", "yield"];
+
+fn syntax(lang: &str) -> Option<&'static Syntax> {
+    match lang.to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "c" | "h" | "cpp" | "cc" | "cxx" | "hpp" => Some(&C_LIKE),
+        "go" | "golang" => Some(&GO),
+        "java" | "csharp" | "cs" | "kotlin" | "kt" | "swift" | "scala" => Some(&TRIPLE_QUOTED),
+        "javascript" | "js" | "jsx" | "mjs" | "cjs" | "typescript" | "ts" | "tsx" | "php" => Some(&JS_LIKE),
+        "css" | "scss" | "less" => Some(&CSS),
+        "python" | "py" => Some(&PYTHON),
+        "ruby" | "rb" => Some(&RUBY),
+        "bash" | "sh" | "shell" | "zsh" => Some(&SHELL),
+        "sql" => Some(&SQL),
+        _ => None,
+    }
+}
+
+// The literal the scanner is inside, if any
+#[derive(Clone, Copy, PartialEq)]
+enum Literal {
+    Quote(char),
+    Raw(char),
+    Triple(char),
+    HashedRaw(usize), // Closed by " and this many #
+    Regex { in_class: bool },
+}
+
+/// Minified code for a known language, one statement line per line.
+pub fn minify(code: &str, lang: &str) -> Option<String> {
+    let syntax = syntax(lang)?;
+    let chars: Vec<char> = code.chars().collect();
+    let starts_with = |i: usize, pattern: &str| pattern.chars().enumerate().all(|(k, c)| chars.get(i + k) == Some(&c));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_in_literal = false; // The line began inside a multi-line literal
+    let mut literal: Option<Literal> = None;
+    let mut pending_space = false;
+    let mut heredocs: Vec<(String, bool)> = Vec::new(); // (terminator, <<- strips tabs) opened on this line
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            // Only multi-line literals carry on to the next line
+            literal = literal.filter(|l| match l {
+                Literal::Quote(q) => syntax.multiline_quotes || *q == '`',
+                Literal::Raw(_) | Literal::Triple(_) | Literal::HashedRaw(_) => true,
+                Literal::Regex { .. } => false,
+            });
+            if line_in_literal || !line.trim().is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.clear();
+            line_in_literal = literal.is_some();
+            pending_space = false;
+            i += 1;
+
+            // Heredoc bodies follow the line that opened them, untouched
+            for (terminator, strip_tabs) in heredocs.drain(..) {
+                while i < chars.len() {
+                    let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |p| i + p);
+                    let body: String = chars[i..end].iter().collect();
+                    i = end + 1;
+                    let done = if strip_tabs { body.trim_start_matches('\t') } else { body.as_str() } == terminator;
+                    lines.push(body);
+                    if done {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        if let Some(current) = literal {
+            line.push(c);
+            match current {
+                Literal::Quote(_) | Literal::Regex { .. } if c == '\\' => {
+                    if let Some(&next) = chars.get(i + 1).filter(|&&next| next != '\n') {
+                        line.push(next);
+                        i += 1;
+                    }
+                }
+                Literal::Quote(q) | Literal::Raw(q) if c == q => literal = None,
+                Literal::Triple(q) if c == q && starts_with(i, &q.to_string().repeat(3)) => {
+                    line.push(q);
+                    line.push(q);
+                    i += 2;
+                    literal = None;
+                }
+                Literal::HashedRaw(hashes) if c == '"' && (1..=hashes).all(|k| chars.get(i + k) == Some(&'#')) => {
+                    line.extend(&chars[i + 1..i + 1 + hashes]);
+                    i += hashes;
+                    literal = None;
+                }
+                Literal::Regex { in_class } => match c {
+                    '[' => literal = Some(Literal::Regex { in_class: true }),
+                    ']' => literal = Some(Literal::Regex { in_class: false }),
+                    '/' if !in_class => literal = None,
+                    _ => {}
+                },
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+
+        // Whitespace outside literals: indentation (Python) or one separating space
+        if c.is_whitespace() {
+            if line.trim().is_empty() && !line_in_literal {
+                if syntax.keep_indent {
+                    line.push(c);
+                }
+            } else {
+                pending_space = true;
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some((open, close)) = syntax.block_comment
+            && starts_with(i, open) {
+            i += open.chars().count();
+            while i < chars.len() && !starts_with(i, close) {
+                // Keep line structure: a line break inside a comment still ends a JS statement
+                if chars[i] == '\n' && !line.trim().is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    pending_space = false;
+                }
+                i += 1;
+            }
+            i += close.chars().count();
+            continue;
+        }
+
+        let at_word_start = i == 0 || chars[i - 1].is_whitespace() || chars[i - 1] == ';';
+        let is_shebang = i == 0 && starts_with(0, "#!");
+        if syntax.line_comments.iter().any(|marker| starts_with(i, marker))
+            && (!syntax.hash_after_space || at_word_start)
+            && !is_shebang {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        if pending_space {
+            line.push(' ');
+            pending_space = false;
+        }
+
+        if syntax.heredocs
+            && let Some((end, terminator, strip_tabs)) = heredoc(&chars, i) {
+            line.extend(&chars[i..end]);
+            heredocs.push((terminator, strip_tabs));
+            i = end;
+            continue;
+        }
+        if syntax.hashed_raw
+            && let Some((quote, hashes)) = hashed_raw_start(&chars, i) {
+            line.extend(&chars[i..=quote]);
+            literal = Some(Literal::HashedRaw(hashes));
+            i = quote + 1;
+            continue;
+        }
+        if syntax.triple_quotes && syntax.quotes.contains(&c) && starts_with(i, &c.to_string().repeat(3)) {
+            line.extend([c, c, c]);
+            literal = Some(Literal::Triple(c));
+            i += 3;
+            continue;
+        }
+        if syntax.raw_quotes.contains(&c) {
+            literal = Some(Literal::Raw(c));
+        } else if syntax.quotes.contains(&c) {
+            literal = Some(Literal::Quote(c));
+        } else if syntax.regex_literals && c == '/' && regex_allowed(&lines, &line) {
+            literal = Some(Literal::Regex { in_class: false });
+        } else if syntax.char_literals && c == '\'' {
+            // 'x' and '\n' are literals; anything else is a lifetime or label
+            let len = if chars.get(i + 1) == Some(&'\\') && chars.get(i + 3) == Some(&'\'') {
+                4
+            } else if chars.get(i + 2) == Some(&'\'') {
+                3
+            } else {
+                1
+            };
+            line.extend(&chars[i..i + len]);
+            i += len;
+            continue;
+        }
+        line.push(c);
+        i += 1;
+    }
+
+    if line_in_literal || !line.trim().is_empty() {
+        lines.push(line);
+    }
+    Some(lines.join("\n"))
+}
+
+/// The opening quote of a Rust raw string (`r"`, `r#"`, `br##"`) starting at
+/// `i`, and its number of #.
+fn hashed_raw_start(chars: &[char], i: usize) -> Option<(usize, usize)> {
+    if i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_') {
+        return None;
+    }
+    let r = if chars.get(i) == Some(&'b') { i + 1 } else { i };
+    if chars.get(r) != Some(&'r') {
+        return None;
+    }
+    let hashes = chars[r + 1..].iter().take_while(|c| **c == '#').count();
+    let quote = r + 1 + hashes;
+    (chars.get(quote) == Some(&'"')).then_some((quote, hashes))
+}
+
+/// A heredoc redirection at `i` (`<<EOF`, `<<-'EOF'`, `<< "EOF"`): where it
+/// ends, its terminator, and whether the body may indent with tabs.
+fn heredoc(chars: &[char], i: usize) -> Option<(usize, String, bool)> {
+    if chars.get(i) != Some(&'<') || chars.get(i + 1) != Some(&'<') || chars.get(i + 2) == Some(&'<') {
+        return None;
+    }
+    let mut j = i + 2;
+    let strip_tabs = chars.get(j) == Some(&'-');
+    if strip_tabs {
+        j += 1;
+    }
+    while chars.get(j).is_some_and(|c| *c == ' ' || *c == '\t') {
+        j += 1;
+    }
+    let quote = chars.get(j).copied().filter(|c| *c == '\'' || *c == '"');
+    if quote.is_some() {
+        j += 1;
+    }
+    let start = j;
+    while chars.get(j).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+        j += 1;
+    }
+    // $((1<<2)) shifts; terminators are words
+    if j == start || chars[start].is_ascii_digit() || (quote.is_some() && chars.get(j).copied() != quote) {
+        return None;
+    }
+    let terminator: String = chars[start..j].iter().collect();
+    Some((j + usize::from(quote.is_some()), terminator, strip_tabs))
+}
+
+/// Whether a / at this point starts a regex literal: at the start of an
+/// expression, after an operator or keyword, but not after a value.
+fn regex_allowed(lines: &[String], line: &str) -> bool {
+    let before = if line.trim().is_empty() { lines.last().map_or("", |l| l.as_str()) } else { line }.trim_end();
+    match before.chars().last() {
+        None => true,
+        Some(')' | ']' | '}' | '"' | '\'' | '`') => false,
+        Some(c) if c.is_alphanumeric() || c == '_' || c == '$' => {
+            let word_start = before
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .map_or(0, |i| i + 1);
+            REGEX_KEYWORDS.contains(&&before[word_start..])
+        }
+        Some(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn min(lang: &str, code: &str) -> String {
+        minify(code, lang).unwrap()
+    }
+
+    #[test]
+    fn c_like_keeps_comment_markers_in_strings_and_chars() {
+        let code = "let url = \"http://x/*y*/\"; // trailing\nlet q = '\"'; /* block */ let s = \"a \\\" // b\";\nfn f<'a>(x: &'a str) {}\n";
+        assert_eq!(
+            min("rust", code),
+            "let url = \"http://x/*y*/\";\nlet q = '\"'; let s = \"a \\\" // b\";\nfn f<'a>(x: &'a str) {}"
+        );
+    }
+
+    #[test]
+    fn rust_raw_strings_are_literal() {
+        let code = "let re = r#\"\"// not  a comment\"#; // comment\nlet s = br\"a /* b\";\nlet t = r##\"x\n  \"# // still\"##;\nlet r = bar(\"x\"); // c\n";
+        assert_eq!(
+            min("rust", code),
+            "let re = r#\"\"// not  a comment\"#;\nlet s = br\"a /* b\";\nlet t = r##\"x\n  \"# // still\"##;\nlet r = bar(\"x\");"
+        );
+    }
+
+    #[test]
+    fn go_raw_strings_have_no_escapes_and_span_lines() {
+        let code = "path := `C:\\dir\\` // windows\nre := `^// [a-z]+\n  # kept as written`\nx := 1 // one\n";
+        assert_eq!(min("go", code), "path := `C:\\dir\\`\nre := `^// [a-z]+\n  # kept as written`\nx := 1");
+    }
+
+    #[test]
+    fn triple_quoted_strings_are_literal() {
+        let kotlin = "val s = \"\"\"\n  // not a comment\n  \"quoted\" /* nor this */\n\"\"\" // comment\n";
+        assert_eq!(min("kotlin", kotlin), "val s = \"\"\"\n  // not a comment\n  \"quoted\" /* nor this */\n\"\"\"");
+        let swift = "let json = \"\"\"\n{\"a\":  1} // keep\n\"\"\"\n";
+        assert_eq!(min("swift", swift), "let json = \"\"\"\n{\"a\":  1} // keep\n\"\"\"");
+        let java = "String html = \"\"\"\n    <p>  // not a comment </p>\n    \"\"\"; // comment\n";
+        assert_eq!(min("java", java), "String html = \"\"\"\n    <p>  // not a comment </p>\n    \"\"\";");
+        let csharp = "var raw = \"\"\"C:\\temp\\ /* x */\"\"\"; // done\n";
+        assert_eq!(min("cs", csharp), "var raw = \"\"\"C:\\temp\\ /* x */\"\"\";");
+    }
+
+    #[test]
+    fn js_regex_literals_are_not_comments() {
+        let code = "const re = /\\/\\/[\"/]+/g; // slashes\nif (/^#/.test(s)) return a / b / c; // math\nreturn /\"/;\n";
+        assert_eq!(min("js", code), "const re = /\\/\\/[\"/]+/g;\nif (/^#/.test(s)) return a / b / c;\nreturn /\"/;");
+        assert_eq!(min("ts", "const t = `line // one\nline /* two */`; // x\n"), "const t = `line // one\nline /* two */`;");
+    }
+
+    #[test]
+    fn python_keeps_indentation_and_docstrings() {
+        let code = "def f():\n    \"\"\"Doc # not a comment\n\n    more\"\"\"\n    # comment\n    s = '#'  # real\n    return  s\n";
+        assert_eq!(min("python", code), "def f():\n    \"\"\"Doc # not a comment\n\n    more\"\"\"\n    s = '#'\n    return s");
+    }
+
+    #[test]
+    fn shell_hashes_and_single_quotes() {
+        let code = "#!/bin/sh\necho 'a\\' # comment\necho \"$#\" x#y # done\ngrep '#' \"multi\n # line\"\n";
+        assert_eq!(min("bash", code), "#!/bin/sh\necho 'a\\'\necho \"$#\" x#y\ngrep '#' \"multi\n # line\"");
+    }
+
+    #[test]
+    fn shell_heredoc_bodies_are_kept() {
+        let code = "cat > run.sh <<'EOF' # write it\n#!/bin/sh\n# comment  kept\n\n  echo   hi\nEOF\necho done # x\ncat <<-END\n\t# tabbed\n\tEND\n";
+        assert_eq!(
+            min("sh", code),
+            "cat > run.sh <<'EOF'\n#!/bin/sh\n# comment  kept\n\n  echo   hi\nEOF\necho done\ncat <<-END\n\t# tabbed\n\tEND"
+        );
+        assert_eq!(min("bash", "grep x <<< \"$s\" # here-string\necho $((1<<2)) # four\n"), "grep x <<< \"$s\"\necho $((1<<2))");
+    }
+
+    #[test]
+    fn sql_and_css_strings() {
+        assert_eq!(min("sql", "SELECT '--x' AS a, 'it''s' -- note\nFROM t; /* c */\n"), "SELECT '--x' AS a, 'it''s'\nFROM t;");
+        assert_eq!(min("css", "a::before { content: \"/*\"; } /* c */\n"), "a::before { content: \"/*\"; }");
+    }
+
+    #[test]
+    fn unknown_languages_are_left_alone() {
+        assert!(minify("x // y", "brainfuck").is_none());
+    }
+}
@@ -0,0 +1,129 @@
+// Target model profiles. The built-ins live in models.toml; --models files
+// add profiles or override built-in fields, and --model must name one of them.
+
+const BUILTIN: &str = include_str!("models.toml");
+
+const KEYS: [&str; 9] = [
+    "base",
+    "context_window",
+    "tokenizer",
+    "chars_per_token",
+    "delimiter",
+    "code_style",
+    "optimize",
+    "chunk_lines",
+    "note",
+];
+const CODE_STYLES: [&str; 2] = ["tagged", "language"];
+const OPTIMIZATIONS: [&str; 3] = ["none", "sections", "code"];
+
+#[derive(Default, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub context_window: usize,
+    pub tokenizer: String,
+    pub chars_per_token: f64,
+    pub delimiter: String,
+    pub code_style: String,
+    pub optimize: String,
+    pub chunk_lines: usize,
+    pub note: String,
+}
+
+impl Profile {
+    pub fn estimate_tokens(&self, chars: usize) -> usize {
+        (chars as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Built-in profiles plus those from each --models file, in order.
+pub fn load(user_files: &[String]) -> Result<Vec<Profile>, String> {
+    let mut profiles = Vec::new();
+    merge(&mut profiles, BUILTIN, "built-in models.toml")?;
+    for path in user_files {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        merge(&mut profiles, &text, path)?;
+    }
+    Ok(profiles)
+}
+
+/// The profile called `name`, or an error listing the known ones.
+pub fn find(profiles: &[Profile], name: &str) -> Result<Profile, String> {
+    profiles.iter().find(|p| p.name == name).cloned().ok_or_else(|| {
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        format!("unknown model '{}' (available: {})", name, names.join(", "))
+    })
+}
+
+fn merge(profiles: &mut Vec<Profile>, text: &str, source: &str) -> Result<(), String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| format!("{}: {}", source, e.message()))?;
+
+    for (name, entry) in &table {
+        let Some(entry) = entry.as_table() else {
+            return Err(format!("{}: [{}] must be a table", source, name));
+        };
+        let fail = |e: String| format!("{} [{}]: {}", source, name, e);
+
+        let mut profile = match profiles.iter().position(|p| &p.name == name) {
+            Some(i) => profiles.remove(i),
+            None => {
+                let base = match entry.get("base") {
+                    Some(toml::Value::String(base)) => base.as_str(),
+                    Some(_) => return Err(fail("'base' must be a string".to_string())),
+                    None => "claude",
+                };
+                // The first built-in has nothing to inherit from
+                let mut profile = find(profiles, base).or_else(|e| if profiles.is_empty() { Ok(Profile::default()) } else { Err(e) }).map_err(fail)?;
+                profile.name = name.clone();
+                profile
+            }
+        };
+        apply_fields(&mut profile, entry).map_err(fail)?;
+        profiles.push(profile);
+    }
+    Ok(())
+}
+
+fn apply_fields(profile: &mut Profile, entry: &toml::Table) -> Result<(), String> {
+    for (key, value) in entry {
+        let string = || value.as_str().map(str::to_string).ok_or(format!("'{}' must be a string", key));
+        let count = || {
+            value.as_integer()
+                .filter(|n| *n > 0)
+                .map(|n| n as usize)
+                .ok_or(format!("'{}' must be a positive integer", key))
+        };
+
+        match key.as_str() {
+            "base" => {}
+            "context_window" => profile.context_window = count()?,
+            "chunk_lines" => profile.chunk_lines = count()?,
+            "tokenizer" => profile.tokenizer = string()?,
+            "delimiter" => profile.delimiter = string()?,
+            "note" => profile.note = string()?,
+            "chars_per_token" => {
+                profile.chars_per_token = value
+                    .as_float()
+                    .or(value.as_integer().map(|n| n as f64))
+                    .filter(|n| *n > 0.0)
+                    .ok_or("'chars_per_token' must be a positive number")?;
+            }
+            "code_style" => {
+                let style = string()?;
+                if !CODE_STYLES.contains(&style.as_str()) {
+                    return Err(format!("invalid code_style '{}' (expected {})", style, CODE_STYLES.join(", ")));
+                }
+                profile.code_style = style;
+            }
+            "optimize" => {
+                let optimize = string()?;
+                if !OPTIMIZATIONS.contains(&optimize.as_str()) {
+                    return Err(format!("invalid optimize '{}' (expected {})", optimize, OPTIMIZATIONS.join(", ")));
+                }
+                profile.optimize = optimize;
+            }
+            _ => return Err(format!("unknown key '{}' (expected {})", key, KEYS.join(", "))),
+        }
+    }
+    Ok(())
+}
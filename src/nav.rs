@@ -0,0 +1,506 @@
+// Static site generator navigation (mdBook SUMMARY.md, MkDocs nav,
+// Docusaurus sidebars/_category_.json), used for file order, section
+// breadcrumbs and the NAV outline at the top of the bundle.
+
+use crate::frontmatter;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use yaml_rust2::{Yaml, YamlLoader};
+
+pub struct NavNode {
+    pub title: String,
+    pub path: Option<PathBuf>, // Canonical path of the page, if it is one
+    pub children: Vec<NavNode>,
+}
+
+pub struct SiteNav {
+    pub kind: &'static str,
+    pub source: Option<PathBuf>, // Canonical path of the navigation file
+    pub nodes: Vec<NavNode>,
+}
+
+impl SiteNav {
+    /// Pages in reading order, with the titles of the sections above them.
+    pub fn pages(&self) -> Vec<(PathBuf, Vec<String>)> {
+        let mut pages = Vec::new();
+        collect_pages(&self.nodes, &mut Vec::new(), &mut pages);
+        pages
+    }
+}
+
+fn collect_pages(nodes: &[NavNode], trail: &mut Vec<String>, pages: &mut Vec<(PathBuf, Vec<String>)>) {
+    for node in nodes {
+        trail.push(node.title.clone());
+        if let Some(path) = &node.path
+            && !pages.iter().any(|(p, _)| p == path) {
+            pages.push((path.clone(), trail.clone()));
+        }
+        collect_pages(&node.children, trail, pages);
+        trail.pop();
+    }
+}
+
+/// Look for a site navigation file in or just above each input directory.
+pub fn detect(inputs: &[String]) -> Option<SiteNav> {
+    for input in inputs {
+        let root = Path::new(input);
+        if !root.is_dir() {
+            continue;
+        }
+
+        for dir in [Some(root), root.parent()].into_iter().flatten() {
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            for name in ["SUMMARY.md", "src/SUMMARY.md", "mkdocs.yml", "mkdocs.yaml", "sidebars.js", "sidebars.ts"] {
+                let candidate = dir.join(name);
+                if !candidate.is_file() {
+                    continue;
+                }
+                match load(&candidate) {
+                    Ok(nav) => {
+                        eprintln!("🧭 Using {} navigation from {}", nav.kind, candidate.display());
+                        return Some(nav);
+                    }
+                    Err(e) => eprintln!("⚠️  Ignoring site navigation in {}: {}", candidate.display(), e),
+                }
+            }
+        }
+
+        // Docusaurus without a sidebars file: autogenerated from _category_.json
+        let has_categories = WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name() == "_category_.json");
+        if has_categories {
+            eprintln!("🧭 Using docusaurus navigation from _category_.json files in {}", root.display());
+            return Some(SiteNav { kind: "docusaurus", source: None, nodes: autogenerated(root) });
+        }
+    }
+
+    None
+}
+
+/// Parse a navigation file, picking the format from its name.
+pub fn load(path: &Path) -> Result<SiteNav, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let source = fs::canonicalize(path).ok();
+
+    let (kind, nodes) = if name.ends_with(".md") {
+        ("mdbook", parse_summary(&text, dir))
+    } else if name.ends_with(".yml") || name.ends_with(".yaml") {
+        ("mkdocs", parse_mkdocs(&text, dir)?)
+    } else if name.starts_with("sidebars.") {
+        ("docusaurus", parse_sidebars(&text, dir)?)
+    } else {
+        return Err(format!("{}: not a recognised navigation file", path.display()));
+    };
+    Ok(SiteNav { kind, source, nodes })
+}
+
+fn parse_summary(text: &str, dir: &Path) -> Vec<NavNode> {
+    let re_item = Regex::new(r"^(\s*)(?:[-*+]\s+)?\[([^\]]+)\]\(([^)]*)\)").unwrap();
+    let re_part = Regex::new(r"^#+\s+(.+)$").unwrap();
+
+    // Open nesting levels as (indent, node); part titles sit below any indent
+    let mut roots = Vec::new();
+    let mut stack: Vec<(isize, NavNode)> = Vec::new();
+    let mut seen_title = false;
+
+    for line in text.lines() {
+        if let Some(caps) = re_part.captures(line) {
+            // The first heading is the book title; later ones start parts
+            close_levels(&mut stack, &mut roots, isize::MIN);
+            if seen_title {
+                stack.push((-1, NavNode { title: caps[1].trim().to_string(), path: None, children: Vec::new() }));
+            }
+            seen_title = true;
+        } else if let Some(caps) = re_item.captures(line) {
+            let indent = caps[1].len() as isize;
+            close_levels(&mut stack, &mut roots, indent);
+            let target = &caps[3];
+            let path = if target.is_empty() { None } else { fs::canonicalize(dir.join(target)).ok() };
+            stack.push((indent, NavNode { title: caps[2].to_string(), path, children: Vec::new() }));
+        }
+    }
+
+    close_levels(&mut stack, &mut roots, isize::MIN);
+    roots
+}
+
+/// Pop every open level at or deeper than `indent` into its parent.
+fn close_levels(stack: &mut Vec<(isize, NavNode)>, roots: &mut Vec<NavNode>, indent: isize) {
+    while stack.last().is_some_and(|(level, _)| *level >= indent) {
+        let (_, node) = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+}
+
+fn parse_mkdocs(text: &str, dir: &Path) -> Result<Vec<NavNode>, String> {
+    // MkDocs configs often carry !!python/name tags; strip them for the parser
+    let re_tags = Regex::new(r"!!?[\w/:.]+").unwrap();
+    let cleaned = re_tags.replace_all(text, "");
+    let docs = YamlLoader::load_from_str(&cleaned).map_err(|e| format!("mkdocs.yml: {}", e))?;
+    let config = docs.first().ok_or("mkdocs.yml is empty")?;
+    let docs_dir = dir.join(config["docs_dir"].as_str().unwrap_or("docs"));
+
+    match &config["nav"] {
+        Yaml::Array(items) => Ok(items.iter().filter_map(|item| mkdocs_node(item, &docs_dir)).collect()),
+        // No nav: MkDocs falls back to the directory listing
+        _ => Ok(autogenerated(&docs_dir)),
+    }
+}
+
+fn mkdocs_node(item: &Yaml, docs_dir: &Path) -> Option<NavNode> {
+    match item {
+        Yaml::String(page) => {
+            let path = fs::canonicalize(docs_dir.join(page)).ok();
+            Some(NavNode { title: page_title(path.as_deref(), page), path, children: Vec::new() })
+        }
+        Yaml::Hash(entry) => {
+            let (title, value) = entry.iter().next()?;
+            let title = title.as_str()?.to_string();
+            match value {
+                Yaml::String(page) if page.contains("://") => None, // External link
+                Yaml::String(page) => Some(NavNode {
+                    title,
+                    path: fs::canonicalize(docs_dir.join(page)).ok(),
+                    children: Vec::new(),
+                }),
+                Yaml::Array(children) => Some(NavNode {
+                    title,
+                    path: None,
+                    children: children.iter().filter_map(|c| mkdocs_node(c, docs_dir)).collect(),
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_sidebars(text: &str, dir: &Path) -> Result<Vec<NavNode>, String> {
+    let docs_dir = dir.join("docs");
+    // Only plain object literals can be read without running the file
+    let re_dynamic = Regex::new(r"\brequire\s*\(|\.\.\.\s*[A-Za-z_$]").unwrap();
+    if re_dynamic.is_match(text) {
+        return Err("sidebars: require() or spread can't be read statically".to_string());
+    }
+    let value = js_literal(text).ok_or("sidebars: could not find the sidebar object")?;
+
+    // Every sidebar is listed in turn, e.g. { docs: [...], api: [...] }
+    let mut nodes = Vec::new();
+    match value {
+        serde_json::Value::Object(sidebars) => {
+            for (_, items) in sidebars {
+                nodes.extend(sidebar_items(&items, &docs_dir));
+            }
+        }
+        items => nodes.extend(sidebar_items(&items, &docs_dir)),
+    }
+    Ok(nodes)
+}
+
+fn sidebar_items(items: &serde_json::Value, docs_dir: &Path) -> Vec<NavNode> {
+    use serde_json::Value;
+
+    let items: Vec<&Value> = match items {
+        Value::Array(list) => list.iter().collect(),
+        // Shorthand { "Category": ["doc-a", "doc-b"] }
+        Value::Object(map) if !map.contains_key("type") => {
+            return map
+                .iter()
+                .map(|(label, children)| NavNode {
+                    title: label.clone(),
+                    path: None,
+                    children: sidebar_items(children, docs_dir),
+                })
+                .collect();
+        }
+        other => vec![other],
+    };
+
+    let mut nodes = Vec::new();
+    for item in items {
+        match item {
+            Value::String(id) => nodes.push(doc_node(id, None, docs_dir)),
+            Value::Object(map) => {
+                let label = map.get("label").and_then(|l| l.as_str()).map(str::to_string);
+                match map.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                    "doc" => {
+                        if let Some(id) = map.get("id").and_then(|i| i.as_str()) {
+                            nodes.push(doc_node(id, label, docs_dir));
+                        }
+                    }
+                    "category" => {
+                        let link = map.get("link")
+                            .and_then(|l| l.get("id"))
+                            .and_then(|i| i.as_str())
+                            .and_then(|id| resolve_doc_id(id, docs_dir));
+                        nodes.push(NavNode {
+                            title: label.unwrap_or_default(),
+                            path: link,
+                            children: map.get("items").map_or_else(Vec::new, |i| sidebar_items(i, docs_dir)),
+                        });
+                    }
+                    "autogenerated" => {
+                        let dir_name = map.get("dirName").and_then(|d| d.as_str()).unwrap_or(".");
+                        nodes.extend(autogenerated(&docs_dir.join(dir_name)));
+                    }
+                    _ if !map.contains_key("type") => nodes.extend(sidebar_items(item, docs_dir)),
+                    _ => {} // Links and HTML items don't map to files
+                }
+            }
+            _ => {}
+        }
+    }
+    nodes
+}
+
+fn doc_node(id: &str, label: Option<String>, docs_dir: &Path) -> NavNode {
+    let path = resolve_doc_id(id, docs_dir);
+    NavNode {
+        title: label.unwrap_or_else(|| page_title(path.as_deref(), id)),
+        path,
+        children: Vec::new(),
+    }
+}
+
+fn resolve_doc_id(id: &str, docs_dir: &Path) -> Option<PathBuf> {
+    fs::canonicalize(docs_dir.join(format!("{}.md", id)))
+        .or_else(|_| fs::canonicalize(docs_dir.join(id).join("index.md")))
+        .ok()
+}
+
+/// Directory-based navigation: categories labelled and positioned by
+/// `_category_.json`, pages by name.
+fn autogenerated(dir: &Path) -> Vec<NavNode> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut items: Vec<(f64, String, NavNode)> = Vec::new();
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            let category: serde_json::Value = fs::read_to_string(path.join("_category_.json"))
+                .ok()
+                .and_then(|text| serde_json::from_str(&text).ok())
+                .unwrap_or_default();
+            let children = autogenerated(&path);
+            if children.is_empty() {
+                continue;
+            }
+            let title = category["label"].as_str().map_or_else(|| name.clone(), str::to_string);
+            let position = category["position"].as_f64().unwrap_or(f64::MAX);
+            items.push((position, name, NavNode { title, path: None, children }));
+        } else if name.ends_with(".md") {
+            let canonical = fs::canonicalize(&path).ok();
+            let title = page_title(canonical.as_deref(), name.trim_end_matches(".md"));
+            // sidebar_position frontmatter wins, then numbered names (01-intro.md);
+            // other pages sort after positioned categories
+            let weight = canonical.as_deref()
+                .and_then(|p| fs::read_to_string(p).ok())
+                .and_then(|text| frontmatter::split(&text, &name).0)
+                .and_then(|fm| fm.weight);
+            let position = weight.map(|w| w as f64).unwrap_or_else(|| {
+                name.split(['-', '_', '.'])
+                    .next()
+                    .and_then(|n| n.parse::<f64>().ok())
+                    .unwrap_or(f64::MAX)
+            });
+            items.push((position, name, NavNode { title, path: canonical, children: Vec::new() }));
+        }
+    }
+
+    items.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    items.into_iter().map(|(_, _, node)| node).collect()
+}
+
+/// Frontmatter title or first `# heading` of a page, falling back to the given name.
+fn page_title(path: Option<&Path>, fallback: &str) -> String {
+    path.and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|text| {
+            let (frontmatter, body) = frontmatter::split(&text, fallback);
+            frontmatter.and_then(|fm| fm.title).or_else(|| {
+                body.lines()
+                    .find_map(|line| line.strip_prefix("# ").map(|t| t.trim().to_string()))
+            })
+        })
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Turn the exported object literal of a sidebars.js/ts file into JSON:
+/// quote bare keys, switch to double quotes, drop comments and trailing commas.
+fn js_literal(text: &str) -> Option<serde_json::Value> {
+    let re_line_comments = Regex::new(r"(?m)^\s*//.*$|\s//[^'\x22\n]*$").unwrap();
+    let re_block_comments = Regex::new(r"(?s)/\*.*?\*/").unwrap();
+    let text = re_block_comments.replace_all(text, "");
+    let text = re_line_comments.replace_all(&text, "");
+
+    let start = text.find(['{', '['])?;
+    let mut json = String::new();
+    let mut depth = 0;
+    let mut chars = text[start..].chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '[' => {
+                depth += 1;
+                json.push(c);
+            }
+            '}' | ']' => {
+                depth -= 1;
+                // Trailing commas are fine in JS but not JSON
+                if json.trim_end().ends_with(',') {
+                    let trimmed = json.trim_end().len() - 1;
+                    json.truncate(trimmed);
+                }
+                json.push(c);
+                if depth == 0 {
+                    break;
+                }
+            }
+            '\'' | '"' | '`' => {
+                let mut value = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        _ if next == c => break,
+                        _ => value.push(next),
+                    }
+                }
+                json.push_str(&serde_json::Value::String(value).to_string());
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut word = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '$' {
+                        word.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match word.as_str() {
+                    "true" | "false" | "null" => json.push_str(&word),
+                    _ => json.push_str(&format!("\"{}\"", word)),
+                }
+            }
+            _ => json.push(c),
+        }
+    }
+
+    serde_json::from_str(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    // A scratch docs site
+    struct Site(TempDir);
+
+    impl Site {
+        fn new(name: &str, files: &[(&str, &str)]) -> Site {
+            Site(TempDir::with_files(&format!("nav-{}", name), files))
+        }
+
+        fn detect(&self, input: &str) -> Option<SiteNav> {
+            detect(&[self.0.input(input)])
+        }
+
+        // Pages relative to the site root, each with its section trail
+        fn pages(&self, nav: &SiteNav) -> Vec<(String, String)> {
+            nav.pages()
+                .into_iter()
+                .map(|(path, trail)| {
+                    let path = path.strip_prefix(&self.0).unwrap().to_string_lossy().replace('\\', "/");
+                    (path, trail.join(" > "))
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn mdbook_summary_nests_items_under_parts() {
+        let site = Site::new("mdbook", &[
+            ("book/src/SUMMARY.md", "# Summary\n\n[Intro](intro.md)\n\n# Guide\n\n- [Setup](guide/setup.md)\n  - [Linux](guide/linux.md)\n- [Draft]()\n"),
+            ("book/src/intro.md", "# Intro\n"),
+            ("book/src/guide/setup.md", "# Setup\n"),
+            ("book/src/guide/linux.md", "# Linux\n"),
+        ]);
+        let nav = site.detect("book/src").unwrap();
+        assert_eq!(nav.kind, "mdbook");
+        assert_eq!(site.pages(&nav), [
+            ("book/src/intro.md".to_string(), "Intro".to_string()),
+            ("book/src/guide/setup.md".to_string(), "Guide > Setup".to_string()),
+            ("book/src/guide/linux.md".to_string(), "Guide > Setup > Linux".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn mkdocs_nav_is_found_above_the_docs_dir() {
+        let site = Site::new("mkdocs", &[
+            ("mkdocs.yml", "site_name: Demo\nmarkdown_extensions:\n  - pymdownx.emoji:\n      emoji_index: !!python/name:material.extensions.emoji.twemoji\nnav:\n  - index.md\n  - Usage:\n    - Install: usage/install.md\n    - GitHub: https://github.com/example\n"),
+            ("docs/index.md", "---\ntitle: Welcome\n---\nHi\n"),
+            ("docs/usage/install.md", "# Install\n"),
+        ]);
+        let nav = site.detect("docs").unwrap();
+        assert_eq!(nav.kind, "mkdocs");
+        assert_eq!(nav.nodes[0].title, "Welcome");
+        assert_eq!(site.pages(&nav), [
+            ("docs/index.md".to_string(), "Welcome".to_string()),
+            ("docs/usage/install.md".to_string(), "Usage > Install".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn docusaurus_sidebars_object_literal() {
+        let site = Site::new("docusaurus", &[
+            ("sidebars.js", "// @ts-check\nconst sidebars = {\n  docs: [\n    'intro',\n    {\n      type: 'category',\n      label: 'API', // Reference pages\n      items: [\"api/client\", {type: 'link', label: 'Site', href: 'https://example.com'},],\n    },\n  ],\n};\nmodule.exports = sidebars;\n"),
+            ("docs/intro.md", "# Introduction\n"),
+            ("docs/api/client.md", "# Client\n"),
+        ]);
+        let nav = site.detect("docs").unwrap();
+        assert_eq!(nav.kind, "docusaurus");
+        assert_eq!(site.pages(&nav), [
+            ("docs/intro.md".to_string(), "Introduction".to_string()),
+            ("docs/api/client.md".to_string(), "API > Client".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn docusaurus_categories_follow_their_position() {
+        let site = Site::new("categories", &[
+            ("docs/b-basics/_category_.json", r#"{"label": "Basics", "position": 1}"#),
+            ("docs/b-basics/start.md", "# Start\n"),
+            ("docs/a-advanced/_category_.json", r#"{"label": "Advanced", "position": 2}"#),
+            ("docs/a-advanced/tuning.md", "# Tuning\n"),
+        ]);
+        let nav = site.detect("docs").unwrap();
+        assert_eq!(site.pages(&nav), [
+            ("docs/b-basics/start.md".to_string(), "Basics > Start".to_string()),
+            ("docs/a-advanced/tuning.md".to_string(), "Advanced > Tuning".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn dynamic_sidebars_are_rejected() {
+        let site = Site::new("dynamic", &[
+            ("sidebars.js", "module.exports = {\n  docs: [...require('./generated'), 'intro'],\n};\n"),
+            ("docs/intro.md", "# Intro\n"),
+        ]);
+        assert!(load(&site.0.join("sidebars.js")).is_err());
+        assert!(site.detect("docs").is_none());
+    }
+}
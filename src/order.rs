@@ -0,0 +1,146 @@
+// File ordering strategies that need file content or an external manifest,
+// applied after the mtime/git ordering done while reading files.
+
+use crate::SourceFile;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn sort_by_path(files: &mut [SourceFile]) {
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+pub fn sort_by_size(files: &mut [SourceFile]) {
+    // Smallest first: short overviews tend to come before long references
+    files.sort_by_key(|file| file.content.len());
+}
+
+pub fn sort_by_weight(files: &mut [SourceFile]) {
+    // Frontmatter weight ascending, unweighted files last in current order
+    files.sort_by_key(|file| file.frontmatter.as_ref().and_then(|fm| fm.weight).unwrap_or(i64::MAX));
+}
+
+/// Order files so that documents linked to come before the documents that
+/// link to them (prerequisites first). Cycles are broken by current order.
+pub fn sort_by_links(files: &mut Vec<SourceFile>) {
+    let index: HashMap<PathBuf, usize> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(i, file)| fs::canonicalize(&file.path).ok().map(|p| (p, i)))
+        .collect();
+
+    // prerequisites[i] = files that file i links to
+    let mut prerequisites: Vec<Vec<usize>> = vec![Vec::new(); files.len()];
+    for (i, file) in files.iter().enumerate() {
+        let Some(dir) = file.path.parent() else { continue };
+        for target in extract_markdown_links(&file.content) {
+            if let Some(j) = resolve_link(dir, &target).and_then(|p| index.get(&p))
+                && *j != i
+                && !prerequisites[i].contains(j) {
+                prerequisites[i].push(*j);
+            }
+        }
+    }
+
+    // Depth-first post-order emits each file after everything it depends on
+    let mut order = Vec::with_capacity(files.len());
+    let mut state = vec![0u8; files.len()]; // 0 new, 1 visiting, 2 done
+    for start in 0..files.len() {
+        visit(start, &prerequisites, &mut state, &mut order);
+    }
+
+    let mut slots: Vec<Option<SourceFile>> = files.drain(..).map(Some).collect();
+    files.extend(order.into_iter().filter_map(|i| slots[i].take()));
+}
+
+fn visit(node: usize, prerequisites: &[Vec<usize>], state: &mut [u8], order: &mut Vec<usize>) {
+    if state[node] != 0 {
+        return; // Done, or a cycle we break here
+    }
+    state[node] = 1;
+    for &dependency in &prerequisites[node] {
+        visit(dependency, prerequisites, state, order);
+    }
+    state[node] = 2;
+    order.push(node);
+}
+
+/// Put files listed in `manifest` first, in manifest order; unlisted files
+/// follow in their current order.
+pub fn sort_by_manifest(files: &mut Vec<SourceFile>, manifest: &Path) -> Result<usize, String> {
+    let listed = manifest_paths(manifest)?;
+    Ok(sort_by_listing(files, &listed))
+}
+
+/// Reorder files to follow `listed` (canonical paths). Returns how many matched.
+pub fn sort_by_listing(files: &mut Vec<SourceFile>, listed: &[PathBuf]) -> usize {
+    let rank: HashMap<&PathBuf, usize> = listed
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p, i))
+        .collect();
+
+    let mut matched = 0;
+    let mut keyed: Vec<(usize, SourceFile)> = files
+        .drain(..)
+        .map(|file| {
+            let key = fs::canonicalize(&file.path).ok().and_then(|p| rank.get(&p).copied());
+            if key.is_some() {
+                matched += 1;
+            }
+            (key.unwrap_or(usize::MAX), file)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    files.extend(keyed.into_iter().map(|(_, file)| file));
+
+    matched
+}
+
+/// Markdown files referenced by an order manifest, in order. Understands
+/// site navigation files (mdBook `SUMMARY.md`, `mkdocs.yml`, Docusaurus
+/// `sidebars.js`) and plain path lists.
+pub fn manifest_paths(manifest: &Path) -> Result<Vec<PathBuf>, String> {
+    let file_name = manifest.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let is_nav = file_name.ends_with(".md")
+        || file_name.ends_with(".yml")
+        || file_name.ends_with(".yaml")
+        || file_name.starts_with("sidebars.");
+    if is_nav {
+        let site = crate::nav::load(manifest)?;
+        return Ok(site.pages().into_iter().map(|(path, _)| path).collect());
+    }
+
+    // Plain list: one path per line, # comments allowed
+    let text = fs::read_to_string(manifest).map_err(|e| format!("{}: {}", manifest.display(), e))?;
+    let dir = manifest.parent().unwrap_or(Path::new("."));
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| resolve_link(dir, line))
+        .collect())
+}
+
+/// Link targets of `[text](target)` in markdown, without titles; images skipped.
+pub fn extract_markdown_links(content: &str) -> Vec<String> {
+    let re_link = Regex::new(r#"(!?)\[[^\]]*\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap();
+    re_link
+        .captures_iter(content)
+        .filter(|caps| caps[1].is_empty())
+        .map(|caps| caps[2].to_string())
+        .collect()
+}
+
+/// Resolve a relative link to a markdown file on disk, dropping any `#anchor`.
+pub fn resolve_link(dir: &Path, target: &str) -> Option<PathBuf> {
+    if target.contains("://") || target.starts_with("mailto:") || target.starts_with('#') {
+        return None;
+    }
+    let path = target.split(['#', '?']).next()?;
+    if !path.ends_with(".md") {
+        return None;
+    }
+    fs::canonicalize(dir.join(path)).ok()
+}
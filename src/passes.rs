@@ -0,0 +1,248 @@
+// Compression passes as named, composable steps. Each level is a list of
+// pass names per output format; a `passes` key in a rules file and then
+// --passes replace the list (`math,lists`) or adjust it (`+math,-articles`).
+// Passes always run in pipeline order, whatever order they are listed in.
+
+use crate::fences;
+use crate::models::Profile;
+use crate::{
+    apply_arrow_notation, apply_extreme_ai_compression, apply_extreme_vrd_compression, apply_mathematical_notation,
+    apply_code_focus, apply_model_optimizations, apply_vrd_abbreviations, compress_code_blocks, compress_formatting,
+    compress_headers_aggressively, compress_lists_aggressively, compress_sentences, compress_vrd_lists,
+    compress_vrd_sentences, optimize_checkboxes, optimize_emphasis, remove_empty_lines, remove_excessive_whitespace,
+    remove_fluff_words, remove_redundant_phrases,
+};
+
+pub trait Pass: Sync {
+    fn name(&self) -> &'static str;
+    fn apply(&self, content: &str, profile: &Profile) -> String;
+}
+
+struct TextPass {
+    name: &'static str,
+    transform: fn(&str) -> String,
+}
+
+impl Pass for TextPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn apply(&self, content: &str, _profile: &Profile) -> String {
+        (self.transform)(content)
+    }
+}
+
+// Passes whose output depends on the target model profile
+struct ModelPass {
+    name: &'static str,
+    transform: fn(&str, &Profile) -> String,
+}
+
+impl Pass for ModelPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn apply(&self, content: &str, profile: &Profile) -> String {
+        (self.transform)(content, profile)
+    }
+}
+
+// Fenced code keeps its indentation and blank lines for the code pass
+fn collapse_whitespace(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let in_code = fences::code_lines(content);
+    let mut output: Vec<String> = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start..lines.len()).find(|&i| in_code[i] != in_code[start]).unwrap_or(lines.len());
+        let run = lines[start..end].join("\n");
+        if in_code[start] {
+            output.push(run);
+        } else {
+            output.extend(Some(remove_empty_lines(&remove_excessive_whitespace(&run))).filter(|run| !run.is_empty()));
+        }
+        start = end;
+    }
+    output.join("\n")
+}
+
+/// Every per-file pass, in pipeline order.
+static PIPELINE: [&dyn Pass; 19] = [
+    &TextPass { name: "whitespace", transform: collapse_whitespace },
+    &TextPass { name: "headers", transform: compress_headers_aggressively },
+    &TextPass { name: "formatting", transform: compress_formatting },
+    &ModelPass { name: "code", transform: compress_code_blocks },
+    &TextPass { name: "lists", transform: compress_lists_aggressively },
+    &TextPass { name: "fluff", transform: remove_fluff_words },
+    &TextPass { name: "sentences", transform: compress_sentences },
+    &TextPass { name: "redundant", transform: remove_redundant_phrases },
+    &TextPass { name: "arrows", transform: apply_arrow_notation },
+    &TextPass { name: "abbreviations", transform: apply_vrd_abbreviations },
+    &TextPass { name: "vrd-lists", transform: compress_vrd_lists },
+    &TextPass { name: "phrases", transform: compress_vrd_sentences },
+    &TextPass { name: "checkboxes", transform: optimize_checkboxes },
+    &TextPass { name: "emphasis", transform: optimize_emphasis },
+    &TextPass { name: "articles", transform: apply_extreme_vrd_compression },
+    &TextPass { name: "math", transform: apply_mathematical_notation },
+    &TextPass { name: "ai", transform: apply_extreme_ai_compression },
+    &ModelPass { name: "code-focus", transform: apply_code_focus },
+    &ModelPass { name: "model", transform: apply_model_optimizations },
+];
+
+/// Passes over the whole bundle rather than one file, run from main. The
+/// content passes come first and are off unless selected here or given a
+/// mode with their own flag (`--tables rows`).
+const CORPUS_PASSES: [&str; 8] = ["tables", "data-blocks", "diagrams", "shell-sessions", "links", "html", "snippets", "dedup"];
+
+const MD_LEVELS: [(&str, &[&str]); 4] = [
+    ("low", &["whitespace", "headers", "formatting", "model"]),
+    ("medium", &["whitespace", "headers", "formatting", "snippets", "dedup", "code", "lists", "fluff", "code-focus", "model"]),
+    ("high", &["whitespace", "headers", "formatting", "snippets", "dedup", "code", "lists", "fluff", "sentences", "redundant", "code-focus", "model"]),
+    ("extreme", &["whitespace", "headers", "formatting", "snippets", "dedup", "code", "lists", "fluff", "sentences", "redundant", "ai", "code-focus", "model"]),
+];
+
+const VRD_LEVELS: [(&str, &[&str]); 4] = [
+    ("low", &["whitespace", "arrows", "abbreviations", "vrd-lists", "phrases", "checkboxes", "emphasis"]),
+    ("medium", &["whitespace", "snippets", "dedup", "arrows", "abbreviations", "vrd-lists", "phrases", "checkboxes", "emphasis", "code-focus"]),
+    ("high", &["whitespace", "snippets", "dedup", "arrows", "abbreviations", "vrd-lists", "phrases", "checkboxes", "emphasis", "articles", "math", "code-focus"]),
+    ("extreme", &["whitespace", "snippets", "dedup", "arrows", "abbreviations", "vrd-lists", "phrases", "checkboxes", "emphasis", "articles", "math", "code-focus"]),
+];
+
+#[derive(Default)]
+pub struct Selection {
+    replace: Option<Vec<String>>,
+    adjustments: Vec<(bool, String)>, // (add, name), applied in order
+}
+
+impl Selection {
+    /// Apply a pass list on top of the selection so far. Plain names replace
+    /// the list; names prefixed with + or - adjust it. The two styles can't
+    /// be mixed in one list.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut replace = Vec::new();
+        let mut adjustments = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let name = if let Some(name) = entry.strip_prefix('+') {
+                adjustments.push((true, name.to_string()));
+                name
+            } else if let Some(name) = entry.strip_prefix('-') {
+                adjustments.push((false, name.to_string()));
+                name
+            } else {
+                replace.push(entry.to_string());
+                entry
+            };
+            if !is_known(name) {
+                return Err(format!("unknown pass '{}' (available: {})", name, names().join(", ")));
+            }
+        }
+
+        if !replace.is_empty() {
+            if !adjustments.is_empty() {
+                return Err("a pass list takes either pass names or +/- adjustments, not both".to_string());
+            }
+            self.replace = Some(replace);
+            self.adjustments.clear();
+        }
+        self.adjustments.extend(adjustments);
+        Ok(())
+    }
+
+    fn selected(&self, format: &str, level: &str, ai_mode: bool) -> Vec<String> {
+        let mut selected: Vec<String> = match &self.replace {
+            Some(replace) => replace.clone(),
+            None => {
+                let levels = if format == "vrd" { &VRD_LEVELS } else { &MD_LEVELS };
+                let defaults = levels.iter().find(|(name, _)| *name == level).map_or(levels[0].1, |(_, passes)| *passes);
+                let mut selected: Vec<String> = defaults.iter().map(|name| name.to_string()).collect();
+                if ai_mode && format != "vrd" {
+                    selected.push("ai".to_string());
+                }
+                selected
+            }
+        };
+        for (add, name) in &self.adjustments {
+            selected.retain(|n| n != name);
+            if *add {
+                selected.push(name.clone());
+            }
+        }
+        selected
+    }
+
+    /// The per-file passes to run for a format and level, in pipeline order.
+    pub fn passes(&self, format: &str, level: &str, ai_mode: bool) -> Vec<&'static dyn Pass> {
+        let selected = self.selected(format, level, ai_mode);
+        PIPELINE
+            .iter()
+            .copied()
+            .filter(|pass| selected.iter().any(|name| name == pass.name()))
+            .collect()
+    }
+
+    /// Whether a bundle-level pass such as `dedup` is selected.
+    pub fn includes(&self, name: &str, format: &str, level: &str, ai_mode: bool) -> bool {
+        self.selected(format, level, ai_mode).iter().any(|n| n == name)
+    }
+}
+
+pub fn run(passes: &[&dyn Pass], content: &str, profile: &Profile) -> String {
+    passes.iter().fold(content.to_string(), |content, pass| pass.apply(&content, profile))
+}
+
+fn is_known(name: &str) -> bool {
+    CORPUS_PASSES.contains(&name) || PIPELINE.iter().any(|pass| pass.name() == name)
+}
+
+fn names() -> Vec<&'static str> {
+    PIPELINE.iter().map(|pass| pass.name()).chain(CORPUS_PASSES).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_pass_leaves_code_indentation() {
+        let content = "Some   text\n\n\n```python\nif x:\n    return  1\n\n```\n\nMore  text\n";
+        assert_eq!(collapse_whitespace(content), "Some text\n```python\nif x:\n    return  1\n\n```\nMore text");
+    }
+
+    #[test]
+    fn rules_file_and_flag_lists_layer_in_order() {
+        let mut selection = Selection::default();
+
+        // Rules file, then --passes
+        selection.apply("+tables,-links").unwrap();
+        selection.apply("+links,-tables").unwrap();
+        assert!(selection.includes("links", "md", "low", false));
+        assert!(!selection.includes("tables", "md", "low", false));
+
+        // A plain list replaces everything before it
+        selection.apply("tables,html").unwrap();
+        assert!(selection.includes("html", "vrd", "extreme", false));
+        assert!(!selection.includes("whitespace", "vrd", "extreme", false));
+    }
+
+    #[test]
+    fn bad_lists_are_rejected() {
+        let mut selection = Selection::default();
+        assert!(selection.apply("tables,-links").unwrap_err().contains("not both"));
+        assert!(selection.apply("+tabels").unwrap_err().contains("unknown pass 'tabels'"));
+        assert!(!selection.includes("links", "md", "medium", false));
+    }
+
+    #[test]
+    fn content_passes_are_opt_in_at_every_level() {
+        let selection = Selection::default();
+        for format in ["md", "vrd"] {
+            for level in ["low", "medium", "high", "extreme"] {
+                let on: Vec<&str> = CORPUS_PASSES.into_iter().filter(|name| selection.includes(name, format, level, true)).collect();
+                let expected: &[&str] = if level == "low" { &[] } else { &["snippets", "dedup"] };
+                assert_eq!(on, expected, "{} {}", format, level);
+            }
+        }
+    }
+}
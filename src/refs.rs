@@ -0,0 +1,226 @@
+// Relative links between bundled documents. `[setup](../guides/setup.md#env)`
+// means nothing once files are concatenated, so targets inside the bundle are
+// rewritten to the F: record they point at: `[setup](→guides/setup.md#env)`,
+// with `@N` appended for the chunk holding that record when chunking.
+
+use crate::{SourceFile, fences};
+use crate::links::{map_prose, outside_code_spans};
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+pub struct RefStats {
+    pub rewritten: usize,
+    pub broken: Vec<(String, String)>, // (file name, link target)
+}
+
+/// Rewrite relative links to other bundled files into `→name#anchor` and
+/// collect links whose target file or heading anchor doesn't exist.
+pub fn resolve_internal_links(files: &mut [SourceFile]) -> RefStats {
+    let re_link = Regex::new(r#"(!?)\[([^\]]*)\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap();
+
+    let mut names: HashMap<PathBuf, usize> = HashMap::new();
+    let mut anchors: Vec<HashSet<String>> = Vec::with_capacity(files.len());
+    for (i, file) in files.iter().enumerate() {
+        if let Ok(path) = fs::canonicalize(&file.path) {
+            names.insert(path, i);
+        }
+        anchors.push(heading_anchors(&file.content));
+    }
+    let targets: Vec<String> = files.iter().map(|file| file.name.clone()).collect();
+
+    let mut stats = RefStats { rewritten: 0, broken: Vec::new() };
+    for (i, file) in files.iter_mut().enumerate() {
+        // Stdin inputs have no real location to resolve against
+        let Some(dir) = file.path.parent().filter(|_| file.path.exists()) else { continue };
+
+        let mut rewritten = 0;
+        let mut broken = Vec::new();
+        let content = map_prose(&file.content, |line| outside_code_spans(line, |text| {
+            re_link.replace_all(text, |caps: &Captures| {
+                let target = &caps[3];
+                if !caps[1].is_empty() || target.contains("://") || target.starts_with("mailto:") {
+                    return caps[0].to_string();
+                }
+
+                let (path, anchor) = match target.split_once('#') {
+                    Some((path, anchor)) => (path, Some(anchor)),
+                    None => (target, None),
+                };
+                let path = path.split('?').next().unwrap_or(path);
+
+                // Same-document anchors only need checking
+                if path.is_empty() {
+                    if let Some(anchor) = anchor
+                        && !anchors[i].contains(anchor) {
+                        broken.push(target.to_string());
+                    }
+                    return caps[0].to_string();
+                }
+                if !path.ends_with(".md") {
+                    return caps[0].to_string();
+                }
+
+                let Ok(resolved) = fs::canonicalize(dir.join(path)) else {
+                    broken.push(target.to_string());
+                    return caps[0].to_string();
+                };
+                // Existing files outside the bundle are left alone
+                let Some(&j) = names.get(&resolved) else {
+                    return caps[0].to_string();
+                };
+                if let Some(anchor) = anchor
+                    && !anchors[j].contains(anchor) {
+                    broken.push(target.to_string());
+                }
+
+                rewritten += 1;
+                match anchor {
+                    Some(anchor) => format!("[{}](→{}#{})", &caps[2], targets[j], anchor),
+                    None => format!("[{}](→{})", &caps[2], targets[j]),
+                }
+            }).to_string()
+        }));
+
+        file.content = content;
+        stats.rewritten += rewritten;
+        stats.broken.extend(broken.into_iter().map(|target| (file.name.clone(), target)));
+    }
+
+    stats
+}
+
+/// Append `@N` to each bundle reference, N being the chunk holding the
+/// referenced record. `record_chunks` maps F: names to 1-based chunk numbers.
+pub fn add_chunk_numbers(content: &str, record_chunks: &HashMap<String, usize>) -> String {
+    let re_ref = Regex::new(r"\]\(→([^)#\s]+)(#[^)\s]*)?\)").unwrap();
+    re_ref.replace_all(content, |caps: &Captures| {
+        match record_chunks.get(&caps[1]) {
+            Some(chunk) => format!("](→{}{}@{})", &caps[1], caps.get(2).map_or("", |m| m.as_str()), chunk),
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
+
+/// The anchors of every heading outside code fences.
+fn heading_anchors(content: &str) -> HashSet<String> {
+    headings(content).into_iter().map(|heading| heading.anchor).collect()
+}
+
+pub struct Heading {
+    pub line: usize,
+    pub level: usize,
+    pub title: String, // Without a trailing {#id}
+    pub anchor: String,
+}
+
+/// Every `#` heading of `content` outside fenced code. An explicit `{#id}`
+/// sets the anchor; otherwise it is the GitHub slug of the title as written,
+/// so "🚀 Quick Start" is `-quick-start` however the title is shown.
+pub fn headings(content: &str) -> Vec<Heading> {
+    let re_explicit_id = Regex::new(r"\s*\{#([^}\s]+)\}\s*$").unwrap();
+    let in_code = fences::code_lines(content);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut headings = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+        let rest = &trimmed[level..];
+        // #hashtag or a bare # is not a heading
+        if in_code[i] || !(1..=6).contains(&level) || !rest.starts_with(' ') || rest.trim().is_empty() {
+            continue;
+        }
+
+        let title = rest.trim();
+        let anchor = match re_explicit_id.captures(title) {
+            Some(caps) => caps[1].to_string(),
+            None => heading_anchor(title, &mut seen),
+        };
+        headings.push(Heading { line: i, level, title: re_explicit_id.replace(title, "").to_string(), anchor });
+    }
+
+    headings
+}
+
+/// GitHub-style anchor for a heading title. `seen` counts earlier slugs in
+/// the same document: repeated headings get -1, -2, ... like GitHub does.
+pub fn heading_anchor(title: &str, seen: &mut HashMap<String, usize>) -> String {
+    let slug = slugify(title);
+    let count = seen.entry(slug.clone()).or_insert(0);
+    let anchor = if *count > 0 { format!("{}-{}", slug, count) } else { slug };
+    *count += 1;
+    anchor
+}
+
+fn slugify(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn resolve(dir: &TempDir, names: &[&str]) -> (Vec<String>, RefStats) {
+        let mut files: Vec<SourceFile> = names
+            .iter()
+            .map(|name| SourceFile {
+                name: name.to_string(),
+                content: fs::read_to_string(dir.join(name)).unwrap(),
+                path: dir.join(name),
+                ..Default::default()
+            })
+            .collect();
+        let stats = resolve_internal_links(&mut files);
+        (files.into_iter().map(|file| file.content).collect(), stats)
+    }
+
+    #[test]
+    fn links_between_bundled_files_become_record_references() {
+        let dir = TempDir::with_files("refs-links", &[
+            ("docs/intro.md", "# Intro\nSee [setup](guide/setup.md#-quick-start), [env](guide/setup.md?x=1#env) and ![logo](logo.md).\n[up](#intro) [gone](#nope) [site](https://example.com/a.md)"),
+            ("docs/guide/setup.md", "# 🚀 Quick Start\n## Environment {#env}\n[back](../intro.md) [missing](../missing.md)"),
+        ]);
+        let (contents, stats) = resolve(&dir, &["docs/intro.md", "docs/guide/setup.md"]);
+        assert_eq!(
+            contents[0],
+            "# Intro\nSee [setup](→docs/guide/setup.md#-quick-start), [env](→docs/guide/setup.md#env) and ![logo](logo.md).\n[up](#intro) [gone](#nope) [site](https://example.com/a.md)"
+        );
+        assert_eq!(contents[1], "# 🚀 Quick Start\n## Environment {#env}\n[back](→docs/intro.md) [missing](../missing.md)");
+        assert_eq!(stats.rewritten, 3);
+        assert_eq!(stats.broken, [("docs/intro.md".to_string(), "#nope".to_string()), ("docs/guide/setup.md".to_string(), "../missing.md".to_string())]);
+    }
+
+    #[test]
+    fn links_in_code_stay_as_written() {
+        let dir = TempDir::with_files("refs-code", &[
+            ("a.md", "~~~text\n```\n[b](b.md)\n~~~\n`[b](b.md)` and [b](b.md)\n````md\n```\n[b](b.md)\n```\n````"),
+            ("b.md", "# B"),
+        ]);
+        let (contents, stats) = resolve(&dir, &["a.md", "b.md"]);
+        assert_eq!(contents[0], "~~~text\n```\n[b](b.md)\n~~~\n`[b](b.md)` and [b](→b.md)\n````md\n```\n[b](b.md)\n```\n````");
+        assert_eq!(stats.rewritten, 1);
+    }
+
+    #[test]
+    fn chunk_numbers_follow_their_records() {
+        let chunks = HashMap::from([("b.md".to_string(), 2)]);
+        assert_eq!(add_chunk_numbers("[x](→b.md#top) [y](→c.md)", &chunks), "[x](→b.md#top@2) [y](→c.md)");
+    }
+
+    #[test]
+    fn heading_anchors_ignore_how_titles_are_shown() {
+        let content = "# 🚀 Quick Start\n## Setup {#install}\n```\n# comment\n```\n## Setup\n## Setup\n#tag";
+        let headings = headings(content);
+        let found: Vec<(usize, &str, &str)> = headings.iter().map(|h| (h.level, h.title.as_str(), h.anchor.as_str())).collect();
+        assert_eq!(found, [(1, "🚀 Quick Start", "-quick-start"), (2, "Setup", "install"), (2, "Setup", "setup"), (2, "Setup", "setup-1")]);
+    }
+}
@@ -0,0 +1,202 @@
+// User-defined rewrite rules, loaded from TOML files given with --rules:
+//
+//   [[rule]]
+//   pattern = "the platform team recommends"
+//   replacement = "recommended:"
+//   case_sensitive = false   # default false
+//   scope = "prose"          # prose (default), headings, code or all
+//   min_level = "medium"     # lowest --level the rule applies at (default low)
+//
+// Rules run line by line before the compression passes, in file order.
+// A top-level `passes = "+tables,-dedup"` sets the pass selection the same
+// way --passes does, so a project's compression setup lives in one file.
+
+use crate::directives::{self, LEVELS};
+use crate::fences;
+use regex::{Regex, RegexBuilder};
+use std::fs;
+
+const SCOPES: [&str; 4] = ["prose", "headings", "code", "all"];
+const KEYS: [&str; 5] = ["pattern", "replacement", "case_sensitive", "scope", "min_level"];
+const TOP_LEVEL_KEYS: [&str; 2] = ["rule", "passes"];
+
+pub struct Rule {
+    pattern: Regex,
+    replacement: String,
+    scope: String,
+    min_level: usize,
+}
+
+pub struct RulesFile {
+    pub rules: Vec<Rule>,
+    pub passes: Option<String>,
+}
+
+/// Load and validate every rule in a rules file, and its pass list if any.
+pub fn load(path: &str) -> Result<RulesFile, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| format!("{}: {}", path, e.message()))?;
+    if let Some(key) = table.keys().find(|key| !TOP_LEVEL_KEYS.contains(&key.as_str())) {
+        return Err(format!("{}: unknown key '{}' (expected {})", path, key, TOP_LEVEL_KEYS.join(", ")));
+    }
+
+    let passes = match table.get("passes") {
+        None => None,
+        Some(toml::Value::String(passes)) => Some(passes.clone()),
+        Some(_) => return Err(format!("{}: 'passes' must be a string like \"+tables,-dedup\"", path)),
+    };
+    let entries = match table.get("rule") {
+        None if passes.is_some() => return Ok(RulesFile { rules: Vec::new(), passes }),
+        None => return Err(format!("{}: no [[rule]] entries", path)),
+        Some(entries) => entries
+            .as_array()
+            .ok_or_else(|| format!("{}: 'rule' must be an array of tables ([[rule]])", path))?,
+    };
+
+    let rules = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| parse_rule(entry).map_err(|e| format!("{} rule {}: {}", path, i + 1, e)))
+        .collect::<Result<Vec<Rule>, String>>()?;
+    Ok(RulesFile { rules, passes })
+}
+
+fn parse_rule(entry: &toml::Value) -> Result<Rule, String> {
+    let Some(entry) = entry.as_table() else {
+        return Err("expected a table".to_string());
+    };
+    if let Some(key) = entry.keys().find(|key| !KEYS.contains(&key.as_str())) {
+        return Err(format!("unknown key '{}' (expected {})", key, KEYS.join(", ")));
+    }
+
+    let string = |key: &str| -> Result<Option<String>, String> {
+        match entry.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("'{}' must be a string", key)),
+        }
+    };
+
+    let Some(pattern) = string("pattern")? else {
+        return Err("missing 'pattern'".to_string());
+    };
+    let replacement = string("replacement")?.unwrap_or_default();
+    let case_sensitive = match entry.get("case_sensitive") {
+        None => false,
+        Some(toml::Value::Boolean(b)) => *b,
+        Some(_) => return Err("'case_sensitive' must be true or false".to_string()),
+    };
+
+    let scope = string("scope")?.unwrap_or_else(|| "prose".to_string());
+    if !SCOPES.contains(&scope.as_str()) {
+        return Err(format!("invalid scope '{}' (expected {})", scope, SCOPES.join(", ")));
+    }
+    let min_level = string("min_level")?.unwrap_or_else(|| "low".to_string());
+    let Some(min_level) = LEVELS.iter().position(|level| *level == min_level) else {
+        return Err(format!("invalid min_level '{}' (expected {})", min_level, LEVELS.join(", ")));
+    };
+
+    let pattern = RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+
+    Ok(Rule { pattern, replacement, scope, min_level })
+}
+
+/// Apply rules to `content`, honouring verdant:level regions. Returns the new
+/// content and the number of replacements made.
+pub fn apply(rules: &[Rule], content: &str, default_level: &str) -> (String, usize) {
+    let level_index = |level: &str| LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+    let mut level = level_index(default_level);
+    let mut replacements = 0;
+    let mut output = Vec::new();
+
+    // Fence lines themselves are never rewritten
+    let re_heading = Regex::new(r"^\s{0,3}#{1,6}(\s|$)").unwrap();
+    let mut scopes: Vec<Option<&str>> = vec![Some("prose"); content.lines().count()];
+    for block in fences::blocks(content) {
+        scopes[block.start..=block.end].fill(Some("code"));
+        scopes[block.start] = None;
+        if block.close.is_some() {
+            scopes[block.end] = None;
+        }
+    }
+
+    for (i, line) in content.lines().enumerate() {
+        if let Some(marker) = directives::level_marker(line) {
+            level = level_index(marker.unwrap_or(default_level));
+            output.push(line.to_string());
+            continue;
+        }
+
+        let scope = match scopes[i] {
+            None => {
+                output.push(line.to_string());
+                continue;
+            }
+            Some("prose") if re_heading.is_match(line) => "headings",
+            Some(scope) => scope,
+        };
+
+        let mut line = line.to_string();
+        for rule in rules {
+            if level < rule.min_level || (rule.scope != "all" && rule.scope != scope) {
+                continue;
+            }
+            let count = rule.pattern.find_iter(&line).count();
+            if count > 0 {
+                replacements += count;
+                line = rule.pattern.replace_all(&line, rule.replacement.as_str()).to_string();
+            }
+        }
+        output.push(line);
+    }
+
+    (output.join("\n"), replacements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: &str, scope: &str) -> Rule {
+        let entry: toml::Value = toml::from_str(&format!(
+            "pattern = {:?}\nreplacement = {:?}\nscope = {:?}\nmin_level = \"medium\"",
+            pattern, replacement, scope
+        ))
+        .unwrap();
+        parse_rule(&entry).unwrap()
+    }
+
+    #[test]
+    fn scopes_follow_real_fences() {
+        let rules = [rule("hi", "HI", "prose"), rule("echo", "ECHO", "code")];
+        let content = "~~~text\n```\necho hi\n~~~\nsay hi\n````md\n```\necho hi\n```\n````";
+        let (output, count) = apply(&rules, content, "medium");
+        assert_eq!(output, "~~~text\n```\nECHO hi\n~~~\nsay HI\n````md\n```\nECHO hi\n```\n````");
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn headings_need_a_space_after_the_hashes() {
+        let rules = [rule("docs", "DOCS", "headings")];
+        let (output, _) = apply(&rules, "## docs\n#docs are great\n#\n####### docs", "medium");
+        assert_eq!(output, "## DOCS\n#docs are great\n#\n####### docs");
+    }
+
+    #[test]
+    fn rules_respect_min_level_regions() {
+        let rules = [rule("very ", "", "all")];
+        let content = "a very big\n<!--verdant:level low-->\na very small\n<!--verdant:end-->\nvery";
+        assert_eq!(apply(&rules, content, "high").0, "a big\n<!--verdant:level low-->\na very small\n<!--verdant:end-->\nvery");
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        let entry: toml::Value = toml::from_str("pattern = \"(\"").unwrap();
+        assert!(parse_rule(&entry).is_err_and(|e| e.starts_with("invalid pattern")));
+        let entry: toml::Value = toml::from_str("pattern = \"a\"\nscope = \"body\"").unwrap();
+        assert!(parse_rule(&entry).is_err_and(|e| e.starts_with("invalid scope")));
+    }
+}
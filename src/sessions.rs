@@ -0,0 +1,167 @@
+// Shell session normalization for bash/sh/console fences. Prompts are
+// stripped, `\` continuations joined into one command line, and output lines
+// (everything after a prompted command up to the next prompt) marked `OUT:`
+// so commands and transcript stay apart once the block is flattened.
+
+use crate::fences;
+use regex::Regex;
+
+const SHELL_LANGS: [&str; 7] = ["bash", "sh", "shell", "zsh", "console", "shell-session", "terminal"];
+
+#[derive(Default)]
+pub struct SessionStats {
+    pub blocks: usize,
+    pub prompts_stripped: usize,
+    pub continuations_joined: usize,
+    pub output_lines_dropped: usize,
+}
+
+/// Normalize every shell fence in `content`. Output beyond `max_output`
+/// lines per command is cut to a `…N more lines` marker (0 drops it all).
+pub fn normalize_blocks(content: &str, max_output: Option<usize>, stats: &mut SessionStats) -> String {
+    fences::map_blocks(content, |block| {
+        if !SHELL_LANGS.contains(&block.lang.as_str()) {
+            return None;
+        }
+        let (session, has_output) = normalize_session(&block.body, &block.lang, max_output, stats);
+        // Transcripts aren't valid shell, so the shell minifier must not see them
+        let lang = if has_output { "console" } else if block.lang == "console" { "bash" } else { block.lang.as_str() };
+        // The rest of the info string (`title="setup.sh"`) stays after the language
+        let info = block.open[block.indent.len() + block.marker.len()..].trim_start();
+        let attributes = info.find(char::is_whitespace).map_or("", |end| &info[end..]);
+        stats.blocks += 1;
+        Some(std::iter::once(format!("{}{}{}{}", block.indent, block.marker, lang, attributes)).chain(session).collect())
+    })
+}
+
+/// The shell prompt of a `lang` block: `$ `, `% ` or `user@host:~$ `, and
+/// `# ` for root only in transcript blocks, where it can't be a comment.
+pub fn prompt_regex(lang: &str) -> Regex {
+    if lang == "console" || lang == "shell-session" || lang == "terminal" {
+        Regex::new(r"^\s*(?:[\w.-]+@[\w.-]+(?::[^$#%\s]*)?\s*)?[$%#] ").unwrap()
+    } else {
+        Regex::new(r"^\s*(?:[\w.-]+@[\w.-]+(?::[^$#%\s]*)?\s*)?[$%] ").unwrap()
+    }
+}
+
+/// The normalized lines of one session, and whether any output remains.
+fn normalize_session(body: &[&str], lang: &str, max_output: Option<usize>, stats: &mut SessionStats) -> (Vec<String>, bool) {
+    let re_prompt = prompt_regex(lang);
+    let prompted = body.iter().any(|line| re_prompt.is_match(line));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut command: Option<String> = None; // A command still being continued
+    let mut output_lines = 0;
+    let mut dropped = 0;
+
+    let mut flush_dropped = |lines: &mut Vec<String>, dropped: &mut usize| {
+        if *dropped > 0 && max_output != Some(0) {
+            lines.push(format!("OUT:…{} more lines", dropped));
+        }
+        stats.output_lines_dropped += *dropped;
+        *dropped = 0;
+    };
+
+    for line in body {
+        if let Some(mut partial) = command.take() {
+            let part = line.trim();
+            partial.push(' ');
+            partial.push_str(part.strip_suffix('\\').unwrap_or(part).trim_end());
+            if part.ends_with('\\') {
+                command = Some(partial);
+            } else {
+                lines.push(partial);
+            }
+            continue;
+        }
+
+        let text = match re_prompt.find(line) {
+            Some(prompt) => {
+                stats.prompts_stripped += 1;
+                flush_dropped(&mut lines, &mut dropped);
+                output_lines = 0;
+                Some(&line[prompt.end()..])
+            }
+            None if prompted => None,
+            None => Some(*line),
+        };
+
+        match text {
+            Some(text) => {
+                let text = text.trim_end();
+                match text.strip_suffix('\\') {
+                    Some(start) => {
+                        stats.continuations_joined += 1;
+                        command = Some(start.trim_end().to_string());
+                    }
+                    None => lines.push(text.to_string()),
+                }
+            }
+            None if line.trim().is_empty() => {}
+            None => {
+                output_lines += 1;
+                if max_output.is_some_and(|max| output_lines > max) {
+                    dropped += 1;
+                } else {
+                    lines.push(format!("OUT:{}", line.trim_end()));
+                }
+            }
+        }
+    }
+    if let Some(partial) = command {
+        lines.push(partial);
+    }
+    flush_dropped(&mut lines, &mut dropped);
+
+    let has_output = lines.iter().any(|line| line.starts_with("OUT:"));
+    (lines, has_output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(content: &str, max_output: Option<usize>) -> String {
+        normalize_blocks(content, max_output, &mut SessionStats::default())
+    }
+
+    #[test]
+    fn prompts_are_stripped_and_output_marked() {
+        let content = "```console\nuser@host:~/app$ ls \\\n  -la\ntotal 0\n\n$ echo done\ndone\n```\n";
+        assert_eq!(normalize(content, None), "```console\nls -la\nOUT:total 0\necho done\nOUT:done\n```\n");
+    }
+
+    #[test]
+    fn hash_is_a_root_prompt_only_in_console_blocks() {
+        let console = "```console\n# apt install jq\nReading package lists...\n```\n";
+        assert_eq!(normalize(console, None), "```console\napt install jq\nOUT:Reading package lists...\n```\n");
+        // In a script, # starts a comment and there is no transcript
+        let script = "```bash\n# install jq\napt install jq\n```\n";
+        assert_eq!(normalize(script, None), script);
+    }
+
+    #[test]
+    fn console_blocks_without_output_become_bash() {
+        let content = "```console\n$ cargo build\n$ cargo test\n```";
+        assert_eq!(normalize(content, None), "```bash\ncargo build\ncargo test\n```");
+    }
+
+    #[test]
+    fn info_string_attributes_are_kept() {
+        let content = "```console title=\"Build\" {1,3}\n$ cargo build\n```";
+        assert_eq!(normalize(content, None), "```bash title=\"Build\" {1,3}\ncargo build\n```");
+    }
+
+    #[test]
+    fn long_output_is_truncated() {
+        let content = "```sh\n$ seq 4\n1\n2\n3\n4\n```\n";
+        assert_eq!(normalize(content, Some(2)), "```console\nseq 4\nOUT:1\nOUT:2\nOUT:…2 more lines\n```\n");
+        assert_eq!(normalize(content, Some(0)), "```sh\nseq 4\n```\n");
+    }
+
+    #[test]
+    fn shell_examples_inside_longer_fences_are_untouched() {
+        let content = "````md\n```bash\n$ ls\nfile\n```\n````\n";
+        assert_eq!(normalize(content, None), content);
+    }
+}
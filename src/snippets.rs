@@ -0,0 +1,99 @@
+// Code blocks repeated across the bundle (install commands, config examples)
+// are stored once in a SNIPPETS: header table and each occurrence replaced by
+// a `SNIP:S1` reference. Blocks match on language plus content, ignoring
+// trailing whitespace and the indent common to the whole block (e.g. a
+// block nested in a list item).
+
+use crate::fences::{self, Block};
+use crate::{SourceFile, compress_code_for_vrd};
+use std::collections::HashMap;
+
+// Shorter blocks cost about as much to reference as to repeat
+const MIN_SNIPPET_CHARS: usize = 30;
+
+pub struct Snippet {
+    pub id: String,
+    pub lang: String,
+    pub code: String,
+    pub uses: usize,
+}
+
+/// Replace every code block that appears more than once with a reference to
+/// a shared snippet. Returns the snippets in order of first appearance.
+pub fn dedup_code_blocks(files: &mut [SourceFile]) -> Vec<Snippet> {
+    // Unclosed blocks run to the end of the file, so only closed ones are shared
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for file in files.iter() {
+        for block in fences::blocks(&file.content).iter().filter(|b| b.close.is_some()) {
+            if let Some(key) = normalize(block) {
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+    }
+
+    let mut snippets: Vec<Snippet> = Vec::new();
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    for file in files.iter_mut() {
+        file.content = fences::replace_blocks(&file.content, |block| {
+            let key = normalize(block).filter(|key| block.close.is_some() && counts[key] > 1)?;
+            let index = *ids.entry(key).or_insert_with(|| {
+                snippets.push(Snippet {
+                    id: format!("S{}", snippets.len() + 1),
+                    lang: block.lang.clone(),
+                    code: block.body.join("\n"),
+                    uses: 0,
+                });
+                snippets.len() - 1
+            });
+            snippets[index].uses += 1;
+            Some(format!("{}SNIP:{}", block.indent, snippets[index].id))
+        });
+    }
+
+    snippets
+}
+
+/// The SNIPPETS: header block, one compacted snippet per line.
+pub fn format_snippet_table(snippets: &[Snippet]) -> String {
+    if snippets.is_empty() {
+        return String::new();
+    }
+    let mut table = String::from("SNIPPETS:\n");
+    for snippet in snippets {
+        let lang = if snippet.lang.is_empty() { String::new() } else { format!("({})", snippet.lang) };
+        table.push_str(&format!("{}{}:{}\n", snippet.id, lang, compress_code_for_vrd(&snippet.code, &snippet.lang)));
+    }
+    table
+}
+
+fn normalize(block: &Block) -> Option<String> {
+    let lines: Vec<&str> = block.body.iter().map(|line| line.trim_end()).collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let body: Vec<&str> = lines.iter().map(|line| line.get(indent..).unwrap_or("")).collect();
+    let body = body.join("\n");
+    (body.trim().len() >= MIN_SNIPPET_CHARS).then(|| format!("{}\n{}", block.lang, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: &str) -> Option<String> {
+        normalize(&fences::blocks(&format!("```py\n{}\n```\n", code.trim_end_matches('\n')))[0])
+    }
+
+    #[test]
+    fn normalize_only_dedents_and_trims_line_ends() {
+        let code = "def greet(name):\n    print(\"hello,  world\")\n";
+        assert_eq!(key(code), key(&code.lines().map(|l| format!("   {}  ", l)).collect::<Vec<_>>().join("\n")));
+        // Relative indentation and string contents still tell blocks apart
+        assert_ne!(key(code), key("def greet(name):\nprint(\"hello,  world\")\n"));
+        assert_ne!(key(code), key("def greet(name):\n    print(\"hello, world\")\n"));
+        assert_eq!(key("short"), None);
+    }
+}
@@ -0,0 +1,271 @@
+// JSON, YAML, TOML and XML code blocks re-emitted in their smallest valid
+// form. Blocks that don't parse (snippets with `...`, JSONC comments) are
+// left for the normal code block handling.
+
+use crate::fences;
+use serde_json::Value as Json;
+use yaml_rust2::parser::{Event, Parser};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Minify every structured-data fence outside other fences. Arrays longer
+/// than `max_items` keep that many items plus a `…N more` marker. Returns the
+/// new content and the number of blocks minified.
+pub fn minify_blocks(content: &str, max_items: Option<usize>) -> (String, usize) {
+    let mut minified = 0;
+    let result = fences::map_blocks(content, |block| {
+        let small = minify(&block.body.join("\n"), &block.lang, max_items)?;
+        minified += 1;
+        Some(vec![block.open.to_string(), small])
+    });
+    (result, minified)
+}
+
+fn minify(code: &str, lang: &str, max_items: Option<usize>) -> Option<String> {
+    match lang {
+        "json" => {
+            let mut value: Json = serde_json::from_str(code).ok()?;
+            truncate_json(&mut value, max_items);
+            serde_json::to_string(&value).ok()
+        }
+        "yaml" | "yml" => {
+            // Tags (!Ref, !!binary) carry meaning the loaded values don't
+            if has_yaml_tags(code) {
+                return None;
+            }
+            let docs = YamlLoader::load_from_str(code).ok()?;
+            let [doc] = docs.as_slice() else { return None };
+            yaml_flow(doc, max_items)
+        }
+        "toml" => {
+            let table: toml::Table = code.parse().ok()?;
+            let lines: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{}={}", toml_key(key), toml_inline(value, max_items)))
+                .collect();
+            Some(lines.join("\n"))
+        }
+        "xml" => minify_xml(code),
+        _ => None,
+    }
+}
+
+fn more_marker(dropped: usize) -> String {
+    format!("…{} more", dropped)
+}
+
+fn truncate_json(value: &mut Json, max_items: Option<usize>) {
+    match value {
+        Json::Array(items) => {
+            if let Some(max) = max_items
+                && items.len() > max {
+                let dropped = items.len() - max;
+                items.truncate(max);
+                items.push(Json::String(more_marker(dropped)));
+            }
+            items.iter_mut().for_each(|item| truncate_json(item, max_items));
+        }
+        Json::Object(map) => map.values_mut().for_each(|item| truncate_json(item, max_items)),
+        _ => {}
+    }
+}
+
+/// YAML in flow style (`{key: value,list: [a,b]}`), quoting only scalars
+/// that would otherwise change meaning.
+fn yaml_flow(value: &Yaml, max_items: Option<usize>) -> Option<String> {
+    Some(match value {
+        Yaml::Hash(map) => {
+            let entries: Option<Vec<String>> = map
+                .iter()
+                .map(|(key, value)| Some(format!("{}: {}", yaml_flow(key, max_items)?, yaml_flow(value, max_items)?)))
+                .collect();
+            format!("{{{}}}", entries?.join(","))
+        }
+        Yaml::Array(items) => {
+            let shown = max_items.map_or(items.len(), |max| max.min(items.len()));
+            let mut entries = items[..shown].iter().map(|item| yaml_flow(item, max_items)).collect::<Option<Vec<_>>>()?;
+            if shown < items.len() {
+                entries.push(yaml_string(&more_marker(items.len() - shown)));
+            }
+            format!("[{}]", entries.join(","))
+        }
+        Yaml::String(s) => yaml_string(s),
+        Yaml::Real(s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        Yaml::Null => "null".to_string(),
+        Yaml::Alias(_) | Yaml::BadValue => return None,
+    })
+}
+
+fn has_yaml_tags(code: &str) -> bool {
+    let mut parser = Parser::new_from_str(code);
+    loop {
+        match parser.next_token() {
+            Ok((Event::StreamEnd, _)) => return false,
+            Ok((Event::Scalar(_, _, _, Some(_)) | Event::SequenceStart(_, Some(_)) | Event::MappingStart(_, Some(_)), _)) => {
+                return true;
+            }
+            Ok(_) => {}
+            Err(_) => return true,
+        }
+    }
+}
+
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.contains([':', '#', ',', '[', ']', '{', '}', '"', '\'', '\n', '\\'])
+        && !s.starts_with(['&', '*', '!', '|', '>', '%', '@', '`', '-', '?'])
+        // A plain scalar must not read back as a number, bool or null
+        && matches!(YamlLoader::load_from_str(s).ok().as_deref(), Some([Yaml::String(_)]));
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).unwrap_or_default() // JSON strings are valid YAML
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+fn toml_inline(value: &toml::Value, max_items: Option<usize>) -> String {
+    match value {
+        toml::Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{}={}", toml_key(key), toml_inline(value, max_items)))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        toml::Value::Array(items) => {
+            let shown = max_items.map_or(items.len(), |max| max.min(items.len()));
+            let mut entries: Vec<String> = items[..shown].iter().map(|item| toml_inline(item, max_items)).collect();
+            if shown < items.len() {
+                entries.push(toml::Value::String(more_marker(items.len() - shown)).to_string());
+            }
+            format!("[{}]", entries.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Drop comments and the indentation between tags. Only well-nested
+/// documents without CDATA or mixed content (`<p>Hi <b>there</b></p>`, where
+/// whitespace between tags is text) are touched.
+fn minify_xml(code: &str) -> Option<String> {
+    if code.contains("<![CDATA[") {
+        return None;
+    }
+    let re_comment = regex::Regex::new(r"<!--[\s\S]*?-->").unwrap();
+    let re_between_tags = regex::Regex::new(r">\s*\n\s*<").unwrap();
+    let re_tag = regex::Regex::new(r"<(/?)([A-Za-z_][\w:.-]*)[^>]*?(/?)>").unwrap();
+
+    let without_comments = re_comment.replace_all(code, "");
+    let mut stack: Vec<(&str, bool, bool)> = Vec::new(); // (name, has text, has child elements)
+    let mut text_start = 0;
+    for caps in re_tag.captures_iter(&without_comments) {
+        let tag = caps.get(0).unwrap();
+        if let Some(parent) = stack.last_mut() {
+            parent.1 |= !without_comments[text_start..tag.start()].trim().is_empty();
+            parent.2 |= &caps[1] != "/";
+            if parent.1 && parent.2 {
+                return None;
+            }
+        }
+        text_start = tag.end();
+
+        let name = caps.get(2).map_or("", |m| m.as_str());
+        if &caps[1] == "/" {
+            if stack.pop().map(|(open, _, _)| open) != Some(name) {
+                return None;
+            }
+        } else if &caps[3] != "/" {
+            stack.push((name, false, false));
+        }
+    }
+    if !stack.is_empty() {
+        return None;
+    }
+
+    let compact = re_between_tags.replace_all(without_comments.trim(), "><");
+    Some(compact.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(lang: &str, code: &str) -> String {
+        format!("Config:\n\n```{}\n{}\n```\n", lang, code)
+    }
+
+    // The minified body of the single block in `content`
+    fn minified(content: &str, max_items: Option<usize>) -> String {
+        let (output, count) = minify_blocks(content, max_items);
+        assert_eq!(count, 1, "{}", output);
+        fences::blocks(&output)[0].body.join("\n")
+    }
+
+    #[test]
+    fn json_is_compacted_and_truncated() {
+        let code = "{\n  \"name\": \"verdant\",\n  \"tags\": [1, 2, 3, 4]\n}";
+        assert_eq!(minified(&block("json", code), None), r#"{"name":"verdant","tags":[1,2,3,4]}"#);
+        assert_eq!(minified(&block("json", code), Some(2)), r#"{"name":"verdant","tags":[1,2,"…2 more"]}"#);
+    }
+
+    #[test]
+    fn yaml_round_trips_through_flow_style() {
+        let code = "server:\n  host: localhost\n  port: 8080\n  debug: \"true\"\nnames:\n  - a: b\n  - 'x, y'\n  - \"#tag\"\n  - ~\nversion: \"1.0\"";
+        let flow = minified(&block("yaml", code), None);
+        assert_eq!(flow, r##"{server: {host: localhost,port: 8080,debug: "true"},names: [{a: b},"x, y","#tag",null],version: "1.0"}"##);
+        assert_eq!(YamlLoader::load_from_str(&flow).unwrap(), YamlLoader::load_from_str(code).unwrap());
+    }
+
+    #[test]
+    fn toml_round_trips_through_inline_tables() {
+        let code = "title = \"Demo\"\n\n[server]\nhost = \"localhost\"\nports = [80, 443]\n\n[\"odd key\"]\nx = 1.5";
+        let inline = minified(&block("toml", code), None);
+        assert_eq!(inline, "title=\"Demo\"\nserver={host=\"localhost\",ports=[80,443]}\n\"odd key\"={x=1.5}");
+        assert_eq!(inline.parse::<toml::Table>().unwrap(), code.parse::<toml::Table>().unwrap());
+    }
+
+    #[test]
+    fn xml_loses_comments_and_whitespace_between_tags() {
+        let code = "<config>\n  <!-- defaults -->\n  <item key=\"a\">1</item>\n  <empty/>\n</config>";
+        assert_eq!(minified(&block("xml", code), None), "<config><item key=\"a\">1</item><empty/></config>");
+    }
+
+    #[test]
+    fn json_numbers_keep_their_digits() {
+        let code = "{\"id\": 123456789012345678901234, \"ratio\": 1.50}";
+        assert_eq!(minified(&block("json", code), None), r#"{"id":123456789012345678901234,"ratio":1.50}"#);
+    }
+
+    #[test]
+    fn tagged_yaml_and_mixed_xml_are_left_alone() {
+        for (lang, code) in [
+            ("yaml", "Value: !Ref Name\nOther: 1"),
+            ("yaml", "data: !!binary aGk="),
+            ("xml", "<p>Hello <b>big</b> <i>world</i></p>"),
+            ("xml", "<p>\n  Text\n  <b>x</b>\n</p>"),
+        ] {
+            let content = block(lang, code);
+            assert_eq!(minify_blocks(&content, None), (content.clone(), 0), "{}", code);
+        }
+        // Spaces between inline tags on one line are text, even without other text
+        assert_eq!(minified(&block("xml", "<p><b>big</b> <i>world</i></p>"), None), "<p><b>big</b> <i>world</i></p>");
+    }
+
+    #[test]
+    fn blocks_that_do_not_parse_are_left_alone() {
+        for (lang, code) in [("json", "{\"a\": 1, ...}"), ("yaml", "a: [b"), ("xml", "<a><b></a>"), ("toml", "x = ")] {
+            let content = block(lang, code);
+            assert_eq!(minify_blocks(&content, None), (content.clone(), 0));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+// Symbol index for API docs: functions, types, CLI commands and environment
+// variables found in fenced code, each mapped to the sections that define or
+// use it. Patterns are deliberately lightweight, one small set per language
+// family, so the index is a map of what exists rather than a parse.
+
+use crate::{SourceFile, fences, refs, sessions};
+use regex::Regex;
+use std::collections::HashMap;
+
+const KINDS: [&str; 4] = ["fn", "type", "cmd", "env"];
+
+const SHELL: &[&str] = &["bash", "sh", "shell", "zsh", "console", "shell-session", "terminal"];
+const C_LIKE: &[&str] = &["c", "h", "cpp", "cc", "cxx", "hpp", "java", "csharp", "cs"];
+
+// Shell words that aren't worth indexing as commands
+const SHELL_BUILTINS: [&str; 24] = [
+    "cd", "echo", "export", "set", "unset", "source", "alias", "if", "then", "else", "elif", "fi", "for", "while",
+    "do", "done", "case", "esac", "sudo", "exit", "return", "local", "read", "printf",
+];
+
+// Control flow that the C-like function pattern would otherwise pick up
+const C_KEYWORDS: [&str; 7] = ["if", "for", "while", "switch", "return", "sizeof", "catch"];
+
+pub struct Symbol {
+    kind: &'static str,
+    name: String,
+    defined: Vec<String>,
+    used: Vec<String>,
+}
+
+// A fenced block or inline code span and the file#section it sits in
+struct Code {
+    lang: Option<String>, // None for inline code
+    text: String,
+    location: String,
+}
+
+struct Patterns {
+    functions: Vec<(&'static [&'static str], Regex)>,
+    types: Regex,
+    env_defined: Regex,
+    env_used: Vec<Regex>,
+}
+
+impl Patterns {
+    fn new() -> Patterns {
+        let re = |pattern: &str| Regex::new(pattern).unwrap();
+        Patterns {
+            functions: vec![
+                (&["rust", "rs"], re(r"\bfn\s+([A-Za-z_]\w*)")),
+                (&["python", "py"], re(r"(?m)^\s*(?:async\s+)?def\s+([A-Za-z_]\w*)")),
+                (&["ruby", "rb"], re(r"(?m)^\s*def\s+(?:self\.)?([A-Za-z_]\w*[?!]?)")),
+                (
+                    &["javascript", "js", "jsx", "mjs", "cjs", "typescript", "ts", "tsx", "php"],
+                    re(r"\bfunction\s*\*?\s*([A-Za-z_$][\w$]*)\s*\("),
+                ),
+                (
+                    &["javascript", "js", "jsx", "mjs", "cjs", "typescript", "ts", "tsx"],
+                    re(r"\b(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*=\s*(?:async\s*)?(?:\([^)]*\)|[A-Za-z_$][\w$]*)\s*=>"),
+                ),
+                (&["go", "golang", "swift"], re(r"\bfunc\s+(?:\([^)]*\)\s*)?([A-Za-z_]\w*)")),
+                (&["kotlin", "kt"], re(r"\bfun\s+(?:<[^>]*>\s*)?(?:\w+\.)?([A-Za-z_]\w*)")),
+                (SHELL, re(r"(?m)^\s*(?:function\s+)?([A-Za-z_][\w-]*)\s*\(\)\s*\{?")),
+                (C_LIKE, re(r"(?m)^\s*(?:[\w:<>\[\],*&]+\s+)+\**([A-Za-z_]\w*)\s*\([^;]*\)\s*\{?\s*$")),
+            ],
+            types: re(r"\b(?:struct|enum|trait|union|class|interface|record|module|protocol|type)\s+([A-Z]\w*)"),
+            env_defined: re(r"(?m)^\s*(?:\$\s+)?(?:export\s+|ENV\s+)?([A-Z][A-Z0-9_]+)="),
+            env_used: vec![
+                re(r"\$\{?([A-Z][A-Z0-9_]+)"),
+                re(r"process\.env\.([A-Z][A-Z0-9_]+)"),
+                re(r#"(?i:getenv|env::var|environ\.get|environ\[)\(?\s*["']([A-Z][A-Z0-9_]+)["']"#),
+            ],
+        }
+    }
+}
+
+/// Collect the functions, types, commands and environment variables in
+/// every file's fenced code, with the sections defining and using each.
+pub fn build_index(files: &[SourceFile]) -> Vec<Symbol> {
+    let patterns = Patterns::new();
+    let code: Vec<Code> = files.iter().flat_map(code_in).collect();
+
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut index: HashMap<(&str, String), usize> = HashMap::new();
+    let mut add = |kind: &'static str, name: &str, location: &str, defined: bool| {
+        let i = *index.entry((kind, name.to_string())).or_insert_with(|| {
+            symbols.push(Symbol { kind, name: name.to_string(), defined: Vec::new(), used: Vec::new() });
+            symbols.len() - 1
+        });
+        let list = if defined { &mut symbols[i].defined } else { &mut symbols[i].used };
+        if !list.iter().any(|l| l == location) {
+            list.push(location.to_string());
+        }
+    };
+
+    for block in &code {
+        let Some(lang) = &block.lang else { continue };
+        let location = block.location.as_str();
+
+        for (langs, re) in &patterns.functions {
+            if !langs.contains(&lang.as_str()) {
+                continue;
+            }
+            for caps in re.captures_iter(&block.text) {
+                if !C_KEYWORDS.contains(&&caps[1]) {
+                    add("fn", &caps[1], location, true);
+                }
+            }
+        }
+        if !SHELL.contains(&lang.as_str()) && !lang.is_empty() {
+            for caps in patterns.types.captures_iter(&block.text) {
+                add("type", &caps[1], location, true);
+            }
+        }
+        if SHELL.contains(&lang.as_str()) || matches!(lang.as_str(), "env" | "dotenv" | "dockerfile") {
+            for caps in patterns.env_defined.captures_iter(&block.text) {
+                add("env", &caps[1], location, true);
+            }
+        }
+        for re in &patterns.env_used {
+            for caps in re.captures_iter(&block.text) {
+                add("env", &caps[1], location, false);
+            }
+        }
+        if SHELL.contains(&lang.as_str()) {
+            for command in shell_commands(&block.text, lang) {
+                add("cmd", &command, location, false);
+            }
+        }
+    }
+
+    // Any mention in code or inline code outside the defining sections is a use
+    for symbol in symbols.iter_mut().filter(|s| s.kind != "cmd") {
+        let re_word = Regex::new(&format!(r"(?:^|[^\w$]){}(?:$|[^\w$])", regex::escape(&symbol.name))).unwrap();
+        for snippet in &code {
+            let location = &snippet.location;
+            if !symbol.defined.contains(location) && !symbol.used.contains(location) && re_word.is_match(&snippet.text) {
+                symbol.used.push(location.clone());
+            }
+        }
+    }
+
+    symbols.sort_by_key(|symbol| KINDS.iter().position(|kind| *kind == symbol.kind));
+    symbols
+}
+
+/// The SYMBOLS: header block: `kind name:def=locations;use=locations`.
+pub fn format_symbol_index(symbols: &[Symbol]) -> String {
+    if symbols.is_empty() {
+        return String::new();
+    }
+    let mut index = String::from("SYMBOLS:\n");
+    for symbol in symbols {
+        let mut parts = Vec::new();
+        if !symbol.defined.is_empty() {
+            parts.push(format!("def={}", symbol.defined.join(",")));
+        }
+        if !symbol.used.is_empty() {
+            parts.push(format!("use={}", symbol.used.join(",")));
+        }
+        index.push_str(&format!("{} {}:{}\n", symbol.kind, symbol.name, parts.join(";")));
+    }
+    index
+}
+
+/// Fenced blocks and inline code spans of a file, located by heading anchor.
+fn code_in(file: &SourceFile) -> Vec<Code> {
+    let re_inline = Regex::new(r"`([^`]+)`").unwrap();
+    let blocks = fences::blocks(&file.content);
+    let in_code = fences::code_lines(&file.content);
+    let headings = refs::headings(&file.content);
+    let mut blocks = blocks.iter().peekable();
+    let mut headings = headings.iter().peekable();
+    let mut code = Vec::new();
+    let mut location = file.name.clone();
+
+    for (i, line) in file.content.lines().enumerate() {
+        if let Some(block) = blocks.next_if(|block| block.start == i) {
+            code.push(Code { lang: Some(block.lang.clone()), text: block.body.join("\n"), location: location.clone() });
+        }
+        if in_code[i] {
+            continue;
+        }
+        if let Some(heading) = headings.next_if(|heading| heading.line == i) {
+            location = format!("{}#{}", file.name, heading.anchor);
+        }
+        for caps in re_inline.captures_iter(line) {
+            code.push(Code { lang: None, text: caps[1].to_string(), location: location.clone() });
+        }
+    }
+
+    code
+}
+
+/// Program names, with a subcommand where one follows (`npm install`), of
+/// each command in a shell block. When some lines carry a prompt, only those
+/// are commands and the rest is output.
+fn shell_commands(text: &str, lang: &str) -> Vec<String> {
+    let re_word = Regex::new(r"^[a-z][a-z0-9-]*$").unwrap();
+    let re_program = Regex::new(r"^[A-Za-z0-9_][\w.+-]*$").unwrap();
+    let re_prompt = sessions::prompt_regex(lang);
+    let prompted = text.lines().any(|line| re_prompt.is_match(line));
+    let mut commands = Vec::new();
+    let mut continued = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let was_continued = continued;
+        continued = trimmed.ends_with('\\');
+        if was_continued {
+            continue;
+        }
+        let command = match re_prompt.find(line) {
+            Some(prompt) => line[prompt.end()..].trim(),
+            None if prompted => continue,
+            None => trimmed,
+        };
+        if command.starts_with('#') {
+            continue;
+        }
+
+        let mut tokens = command.split_whitespace().skip_while(|t| t.contains('=') || *t == "sudo");
+        let Some(program) = tokens.next() else { continue };
+        if !re_program.is_match(program) || SHELL_BUILTINS.contains(&program) {
+            continue;
+        }
+        let command = match tokens.next() {
+            Some(sub) if re_word.is_match(sub) => format!("{} {}", program, sub),
+            _ => program.to_string(),
+        };
+        if !commands.contains(&command) {
+            commands.push(command);
+        }
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(content: &str) -> String {
+        let file = SourceFile { name: "a.md".to_string(), content: content.to_string(), ..Default::default() };
+        format_symbol_index(&build_index(&[file]))
+    }
+
+    #[test]
+    fn definitions_and_uses_are_located_by_anchor() {
+        let content = "# 🚀 Quick Start\n```rust\npub struct Config;\nfn load() -> Config {}\n```\n## Usage\nCall `load()`.\n```sh\nexport API_KEY=1\n```";
+        assert_eq!(
+            index(content),
+            "SYMBOLS:\nfn load:def=a.md#-quick-start;use=a.md#usage\ntype Config:def=a.md#-quick-start\nenv API_KEY:def=a.md#usage\n"
+        );
+    }
+
+    #[test]
+    fn commands_follow_session_prompts() {
+        let content = "```console\nuser@host:~/app$ npm install \\\n  --save\nadded 1 package\n# apt install jq\n% cargo build\n```";
+        let commands: Vec<String> = code_in(&SourceFile { content: content.to_string(), ..Default::default() })
+            .iter()
+            .flat_map(|code| shell_commands(&code.text, code.lang.as_deref().unwrap_or("")))
+            .collect();
+        assert_eq!(commands, ["npm install", "apt install", "cargo build"]);
+        // In a script, # is a comment and every line is a command
+        assert_eq!(shell_commands("# setup\nmake all\n", "bash"), ["make all"]);
+    }
+
+    #[test]
+    fn code_after_mixed_fences_is_still_found() {
+        let content = "~~~text\n```\n~~~\n## Build\n```bash\nmake test\n```\n````md\n```python\ndef hidden(): pass\n```\n````";
+        let code = code_in(&SourceFile { name: "a.md".to_string(), content: content.to_string(), ..Default::default() });
+        let found: Vec<(Option<&str>, &str, &str)> =
+            code.iter().map(|c| (c.lang.as_deref(), c.text.as_str(), c.location.as_str())).collect();
+        assert_eq!(found, [
+            (Some("text"), "```", "a.md"),
+            (Some("bash"), "make test", "a.md#build"),
+            (Some("md"), "```python\ndef hidden(): pass\n```", "a.md#build"),
+        ]);
+    }
+}
@@ -0,0 +1,111 @@
+// Compact encoding for GFM tables. The `| --- |` alignment row, outer pipes
+// and cell padding are pure overhead for a model; the header is kept once.
+//
+//   rows: TBL[Name|Type|Default]      kv: TBL[Option=Meaning]
+//         input|string|.                   --input=Input directory
+
+use crate::fences;
+use regex::Regex;
+
+/// Rewrite every table outside code fences. Returns the new content and the
+/// number of tables rewritten. `mode` is auto, rows or kv.
+pub fn compact_tables(content: &str, mode: &str) -> (String, usize) {
+    let re_delimiter = Regex::new(r"^\s*\|?\s*:?-{3,}:?\s*(\|\s*:?-{3,}:?\s*)*\|?\s*$").unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output = Vec::with_capacity(lines.len());
+    let in_code = fences::code_lines(content);
+    let mut tables = 0;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let is_table = !in_code[i]
+            && line.contains('|')
+            && lines.get(i + 1).is_some_and(|next| re_delimiter.is_match(next));
+        if !is_table {
+            output.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        let header = split_cells(line);
+        let mut rows = Vec::new();
+        i += 2;
+        while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+            rows.push(split_cells(lines[i]));
+            i += 1;
+        }
+
+        let use_kv = mode == "kv" || (mode == "auto" && header.len() == 2);
+        if use_kv && header.len() == 2 {
+            output.push(format!("TBL[{}={}]", header[0], header[1]));
+            for row in rows {
+                let key = row.first().map_or("", |c| c.as_str());
+                let value = row.get(1).map_or("", |c| c.as_str());
+                output.push(format!("{}={}", key, value));
+            }
+        } else {
+            output.push(format!("TBL[{}]", header.join("|")));
+            for row in rows {
+                output.push(row.join("|"));
+            }
+        }
+        tables += 1;
+    }
+
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    (result, tables)
+}
+
+/// Cells of a table row, without outer pipes or padding. `\|` stays escaped.
+fn split_cells(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('|').filter(|_| !trimmed.ends_with("\\|")).unwrap_or(trimmed);
+
+    let mut cells = Vec::new();
+    let mut current = String::new();
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                current.push_str("\\|");
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    cells.push(current.trim().to_string());
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_column_tables_become_key_value_records() {
+        let table = "| Option | Meaning |\n|:---|---:|\n| `--input` | Input directory |\n| `-o` | Output \\| file |\n\nAfter\n";
+        let (compacted, count) = compact_tables(table, "auto");
+        assert_eq!(count, 1);
+        assert_eq!(compacted, "TBL[Option=Meaning]\n`--input`=Input directory\n`-o`=Output \\| file\n\nAfter\n");
+    }
+
+    #[test]
+    fn wider_tables_keep_the_header_once() {
+        let table = "Name | Type | Default\n--- | --- | ---\ninput | string |\n";
+        assert_eq!(compact_tables(table, "auto").0, "TBL[Name|Type|Default]\ninput|string\n");
+        let two = "| a | b |\n| --- | --- |\n| 1 | 2 |";
+        assert_eq!(compact_tables(two, "rows").0, "TBL[a|b]\n1|2");
+    }
+
+    #[test]
+    fn tables_in_code_blocks_are_left_alone() {
+        let content = "````md\n```\n| a | b |\n| --- | --- |\n```\n| c | d |\n| --- | --- |\n````\n";
+        assert_eq!(compact_tables(content, "auto"), (content.to_string(), 0));
+    }
+}
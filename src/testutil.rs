@@ -0,0 +1,60 @@
+// Scratch directories for tests: a fresh tree under the system temp dir,
+// named per test and process so parallel tests never share one, and removed
+// when dropped.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// An empty `verdant-<name>-<pid>` directory. The path is canonical, so
+    /// it compares equal to paths resolved from inside it.
+    pub fn new(name: &str) -> TempDir {
+        let root = std::env::temp_dir().join(format!("verdant-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        TempDir(fs::canonicalize(&root).unwrap())
+    }
+
+    /// A directory holding `files`, given as (relative path, content).
+    pub fn with_files(name: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(name);
+        for (file, content) in files {
+            dir.write(file, content);
+        }
+        dir
+    }
+
+    pub fn write(&self, file: &str, content: &str) {
+        let path = self.0.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+    }
+
+    /// `relative` under the directory, as an input argument.
+    pub fn input(&self, relative: &str) -> String {
+        self.0.join(relative).to_string_lossy().to_string()
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}